@@ -0,0 +1,108 @@
+use super::{knowledge_base::KnowledgeBaseLiteral, solver::Solver, variable::Variable, KnowledgeBase, VariableNames};
+
+/**
+    The literals that are true in every model of a KB
+
+    Found by solving once, then for every literal in that model
+    checking if the KB is still satisfiable with the literal negated.
+    Every model found along the way rules out more candidates.
+
+    A variable that is not in the backbone is free, it can be either true or false.
+*/
+#[derive(Debug)]
+pub(super) enum Backbone{
+    Contradiction,
+    Literals{
+        literals: Vec<KnowledgeBaseLiteral>,
+        free: Vec<Variable>
+    }
+}
+impl Backbone{
+    /// variables are the ones to report on, usually every named variable
+    pub(super) fn from_knowledge_base(kb: &KnowledgeBase, variables: &[Variable])->Self{
+        let mut solver = Solver::new(kb);
+        let Some(model) = solver.solve() else {return Self::Contradiction};
+
+        let mut candidates: Vec<Option<KnowledgeBaseLiteral>> = variables
+            .iter()
+            .map(|var|model.value(var).map(|value|KnowledgeBaseLiteral::new(!value, *var)))
+            .collect();
+
+        for i in 0..candidates.len() {
+            let Some(candidate) = candidates[i].clone() else {continue};
+            let Some(other) = solver.solve_with_assumptions(&[candidate.negated()]) else {continue};
+
+            for candidate in candidates.iter_mut() {
+                let flipped = candidate
+                    .as_ref()
+                    .is_some_and(|literal|other.value(literal.var()) != Some(!literal.not()));
+                if flipped {
+                    *candidate = None;
+                }
+            }
+        }
+
+        let mut literals = Vec::new();
+        let mut free = Vec::new();
+        for (var, candidate) in variables.iter().zip(candidates) {
+            match candidate {
+                Some(literal) => literals.push(literal),
+                None => free.push(*var),
+            }
+        }
+        Self::Literals{literals, free}
+    }
+    pub(super) fn display(&self, vars: &VariableNames)->String{
+        let Self::Literals{literals, free} = self else {return "Contradiction, the knowledge base has no models\n".to_string()};
+
+        let mut lines: Vec<(&Variable, &str)> = literals
+            .iter()
+            .map(|literal|(literal.var(), if literal.not() {"false"} else {"true"}))
+            .chain(free.iter().map(|var|(var, "free")))
+            .collect();
+        lines.sort();
+
+        let mut out = String::new();
+        for (var, value) in lines {
+            let Some(var_name) = vars.get_name_from_variable(var) else {unreachable!()};
+            out.push_str(var_name);
+            out.push_str(": ");
+            out.push_str(value);
+            out.push('\n');
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::test_helpers::{models, random_facts, Random};
+
+    /// A literal is in the backbone when every model has it, checked by trying every assignment
+    #[test]
+    fn matches_brute_force(){
+        let mut random = Random(0xA0761D6478BD642F);
+        let variables = 6;
+        let all: Vec<Variable> = (0..variables).map(Variable::new).collect();
+        for _ in 0..300 {
+            let count = random.below(12);
+            let facts = random_facts(&mut random, count, 0..variables);
+            let models = models(&facts, variables);
+
+            match Backbone::from_knowledge_base(&KnowledgeBase::from_facts(facts), &all) {
+                Backbone::Contradiction => assert!(models.is_empty()),
+                Backbone::Literals{literals, free} => {
+                    assert!(!models.is_empty());
+                    for var in all.iter() {
+                        let values: Vec<bool> = models.iter().map(|model|model >> var.index() & 1 == 1).collect();
+                        let fixed = values.iter().all(|value|*value == values[0]).then_some(values[0]);
+                        let found = literals.iter().find(|literal|literal.var() == var).map(|literal|!literal.not());
+                        assert_eq!(found, fixed);
+                        assert_eq!(free.contains(var), fixed.is_none());
+                    }
+                },
+            }
+        }
+    }
+}
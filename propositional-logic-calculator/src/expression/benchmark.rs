@@ -0,0 +1,182 @@
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+use super::{dag::ExpressionDag, examples::{Queens, Sudoku}, expression::{Expression, ExpressionNode}, knowledge_base::{KnowledgeBaseFact, KnowledgeBaseLiteral}, random::Random, simplifier::Simplifier, variable::Variable, KnowledgeBase};
+
+/// Compares the occurrence list simplifier with the old pairwise one
+/// Run with --bench-simplify
+pub fn benchmark_simplify()->String{
+    let mut out = format!("{:>8} {:>12} {:>12} {:>8}\n", "facts", "quadratic", "occurrence", "kept");
+    for size in [250, 500, 1000, 2000, 4000] {
+        let facts = generate(size, &mut Random(0x2545F4914F6CDD1D ^ size as u64));
+
+        //the simplifier tests check both give the same facts
+        let (_, old_time) = time(||simplify_quadratic(facts.clone()));
+        let (new, new_time) = time(||Simplifier::new(facts.clone()).run());
+
+        out.push_str(&format!("{:>8} {:>12?} {:>12?} {:>8}\n", size, old_time, new_time, new.len()));
+    }
+    out
+}
+
+/// Compares converting through the dag with rewriting the tree
+/// on a chain of biconditionals, where the tree doubles in size every step
+/// and so do the facts unless shared links get fresh variables
+/// Run with --bench-cnf
+pub fn benchmark_cnf()->String{
+    let mut out = format!("{:>8} {:>12} {:>12} {:>10} {:>8} {:>12} {:>8}\n", "length", "tree", "dag", "dag nodes", "facts", "defined", "facts");
+    for length in [2, 3, 4, 5, 6, 8, 10, 12, 16, 64, 256] {
+        let node = (1..length).fold(ExpressionNode::new_variable(Variable::new(0)), |chain, i|{
+            ExpressionNode::new_biconditional(chain, ExpressionNode::new_variable(Variable::new(i)))
+        });
+        let expression = Expression::from_node(node);
+
+        //the tree runs out of memory at 7 and plain distributing takes too long past 12
+        let tree_time = (length <= 6).then(||time(||KnowledgeBase::from_expression_tree(expression.clone())).1);
+        let dag = (length <= 12).then(||time(||KnowledgeBase::from_expression(expression.clone())));
+        let ((defined_kb, _), defined_time) = time(||KnowledgeBase::from_expression_defined(expression.clone(), length));
+
+        let show = |time: Option<Duration>|time.map(|time|format!("{:?}", time)).unwrap_or("-".to_string());
+        let (dag_time, dag_facts) = match dag {
+            Some((kb, time)) => (show(Some(time)), kb.facts().len().to_string()),
+            None => (show(None), "-".to_string()),
+        };
+        out.push_str(&format!("{:>8} {:>12} {:>12} {:>10} {:>8} {:>12?} {:>8}\n", length, show(tree_time), dag_time, dag_nodes(&expression), dag_facts, defined_time, defined_kb.facts().len()));
+    }
+    out
+}
+fn dag_nodes(expression: &Expression)->usize{
+    let mut dag = ExpressionDag::new();
+    dag.add(expression.node());
+    dag.len()
+}
+
+fn time<T>(f: impl FnOnce()->T)->(T, Duration){
+    let start = Instant::now();
+    let out = f();
+    (out, start.elapsed())
+}
+
+/// Random facts of 3 to 6 literals over 250 variables, with a few positive units
+/// and some copies of earlier facts with an extra literal so there is something to subsume
+pub(super) fn generate(size: usize, random: &mut Random)->Vec<KnowledgeBaseFact>{
+    let mut facts: Vec<KnowledgeBaseFact> = Vec::new();
+    let literal = |random: &mut Random|KnowledgeBaseLiteral::new(random.below(2) == 0, Variable::new(random.below(250) as u32));
+
+    while facts.len() < size {
+        let fact = match random.below(200) {
+            0 => vec![KnowledgeBaseLiteral::new(false, Variable::new(random.below(250) as u32))],
+            1..=40 if !facts.is_empty() => {
+                let mut copy = facts[random.below(facts.len() as u64) as usize].literals().clone();
+                copy.push(literal(random));
+                copy
+            },
+            _ => (0..3 + random.below(4)).map(|_|literal(random)).collect(),
+        };
+        facts.push(KnowledgeBaseFact::new(fact));
+    }
+    facts
+}
+
+/// How KnowledgeBase::simplify used to work, every fact against every other until nothing changes
+pub(super) fn simplify_quadratic(mut facts: Vec<KnowledgeBaseFact>)->Vec<KnowledgeBaseFact>{
+    let mut run_again = true;
+
+    while run_again {
+        run_again = false;
+
+        let mut out = Vec::new();
+        let mut units = HashSet::new();
+
+        //remove superset & duplicates & tautologies
+        for fact_a in facts.iter(){
+
+            if out.contains(fact_a) || fact_a.tautology() {
+                run_again = true;
+                continue;
+            }
+
+            if !facts
+                .iter()
+                .any(|fact_b|{
+                    fact_b.is_subset(fact_a) && !fact_a.is_subset(fact_b)
+                })
+            {
+                if let Some(literal) = fact_a.is_unit() {
+                    units.insert(literal);
+                }
+                out.push(fact_a.clone());
+            }else{
+                run_again = true;
+            }
+        }
+
+        facts = out
+            .into_iter()
+            .map(|mut fact|{
+                if fact.filter_negative_literals(&units) {
+                    run_again = true;
+                }
+                fact
+            })
+            .collect();
+    }
+    facts
+}
+
+/// The hardest of the classic sudokus, hardly anything follows from the givens
+const HARD_SUDOKU: &str = "
+1....7.9.
+.3..2...8
+..96..5..
+..53..9..
+.1..8...2
+6....4...
+3......1.
+.4......7
+..7...3..
+";
+
+/// Encodes and solves the built in examples, checking every answer follows the rules
+/// Run with --bench-examples
+pub fn benchmark_examples()->String{
+    let mut out = format!("{:>12} {:>12} {:>12} {:>10} {:>8}\n", "example", "encode", "solve", "variables", "facts");
+    for size in [8, 16, 32, 48] {
+        let queens = Queens::new(size);
+        let (mut workspace, encode_time) = time(||queens.workspace());
+        let (model, solve_time) = time(||workspace.solve());
+        assert!(model.is_some(), "{} queens has an answer", size);
+        let Some(solved) = queens.solve() else {unreachable!("it was just solved")};
+        let columns = solved.columns();
+        for (r, c) in columns.iter().enumerate() {
+            for (other_r, other_c) in columns.iter().enumerate().skip(r + 1) {
+                assert!(c != other_c && other_r - r != c.abs_diff(*other_c), "queens {} and {} can take each other", r, other_r);
+            }
+        }
+        let facts = workspace.expression_set.knowledge_base().facts().len();
+        out.push_str(&format!("{:>12} {:>12?} {:>12?} {:>10} {:>8}\n", format!("{} queens", size), encode_time, solve_time, workspace.variable_names().variables().len(), facts));
+    }
+
+    let Ok(sudoku) = Sudoku::parse(HARD_SUDOKU) else {unreachable!("the grid is written right")};
+    let (mut workspace, encode_time) = time(||sudoku.workspace());
+    let (model, solve_time) = time(||workspace.solve());
+    assert!(model.is_some(), "the sudoku has an answer");
+    let Some(solved) = sudoku.solve() else {unreachable!("it was just solved")};
+    for i in 0..9 {
+        let mut row: Vec<u8> = (0..9).map(|c|solved.cell(i, c)).collect();
+        let mut column: Vec<u8> = (0..9).map(|r|solved.cell(r, i)).collect();
+        let mut square: Vec<u8> = (0..9).map(|k|solved.cell(i / 3 * 3 + k / 3, i % 3 * 3 + k % 3)).collect();
+        for group in [&mut row, &mut column, &mut square] {
+            group.sort();
+            assert_eq!(*group, (1..=9).collect::<Vec<u8>>(), "a row, column or box is missing a number");
+        }
+    }
+    for r in 0..9 {
+        for c in 0..9 {
+            assert!(sudoku.cell(r, c) == 0 || sudoku.cell(r, c) == solved.cell(r, c), "a given changed");
+        }
+    }
+    let facts = workspace.expression_set.knowledge_base().facts().len();
+    out.push_str(&format!("{:>12} {:>12?} {:>12?} {:>10} {:>8}\n", "sudoku", encode_time, solve_time, workspace.variable_names().variables().len(), facts));
+    out
+}
@@ -0,0 +1,74 @@
+use std::ops::{BitAnd, BitOr, BitXor, Not};
+
+use super::{variable::Variable, Expression};
+
+/**
+    A variable to build expressions out of, get one from Workspace::var or VariableNames::var
+
+    & | ^ and ! work on vars, expressions and bools and always give an expression,
+    so (a & b) | !c builds the same expression as parsing "a & b | !c"
+    implies, iff, nand and nor are methods because rust has no operators for them
+
+    A var only means something to the names it came from
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Var(Variable);
+impl Var{
+    pub(super) fn new(variable: Variable)->Self{
+        Self(variable)
+    }
+    pub fn variable(&self)->Variable{
+        self.0
+    }
+    pub fn implies(self, other: impl Into<Expression>)->Expression{
+        Expression::from(self).implies(other)
+    }
+    pub fn iff(self, other: impl Into<Expression>)->Expression{
+        Expression::from(self).iff(other)
+    }
+    pub fn nand(self, other: impl Into<Expression>)->Expression{
+        Expression::from(self).nand(other)
+    }
+    pub fn nor(self, other: impl Into<Expression>)->Expression{
+        Expression::from(self).nor(other)
+    }
+}
+
+impl From<Var> for Expression{
+    fn from(var: Var)->Self{
+        Expression::variable(var.0)
+    }
+}
+/// true is 1 and false is 0
+impl From<bool> for Expression{
+    fn from(value: bool)->Self{
+        Expression::constant(value)
+    }
+}
+
+macro_rules! binary_operator {
+    ($operator:ident, $method:ident, $build:ident) => {
+        impl<T: Into<Expression>> $operator<T> for Expression{
+            type Output = Expression;
+            fn $method(self, other: T)->Expression{
+                self.$build(other)
+            }
+        }
+        impl<T: Into<Expression>> $operator<T> for Var{
+            type Output = Expression;
+            fn $method(self, other: T)->Expression{
+                Expression::from(self).$build(other)
+            }
+        }
+    };
+}
+binary_operator!(BitAnd, bitand, and);
+binary_operator!(BitOr, bitor, or);
+binary_operator!(BitXor, bitxor, xor);
+
+impl Not for Var{
+    type Output = Expression;
+    fn not(self)->Expression{
+        !Expression::from(self)
+    }
+}
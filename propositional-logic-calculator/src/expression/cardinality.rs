@@ -0,0 +1,98 @@
+use super::{expression::Expression, knowledge_base::{KnowledgeBaseFact, KnowledgeBaseLiteral}, variable::Variable};
+
+/// Every way to pick size of the items, in order
+fn combinations<T: Clone>(items: &[T], size: usize)->Vec<Vec<T>>{
+    if size == 0 {
+        return vec![Vec::new()];
+    }
+    let mut out = Vec::new();
+    //past this there aren't enough left to pick from
+    for i in 0..(items.len() + 1).saturating_sub(size) {
+        for mut rest in combinations(&items[i + 1..], size - 1) {
+            rest.insert(0, items[i].clone());
+            out.push(rest);
+        }
+    }
+    out
+}
+/// Joins with and, true if there is nothing to join
+fn all(expressions: impl IntoIterator<Item = Expression>)->Expression{
+    expressions.into_iter().reduce(|a, b|a & b).unwrap_or(Expression::constant(true))
+}
+/**
+    At most count of the literals are true
+    No count + 1 of them are all true, so this has one part for every way to pick count + 1,
+    fine for a handful of literals or a count of 1 but it grows fast, at_most_facts doesn't
+*/
+pub(super) fn at_most(literals: &[Expression], count: usize)->Expression{
+    if count >= literals.len() {
+        return Expression::constant(true);
+    }
+    all(combinations(literals, count + 1).into_iter().map(|picked|!all(picked)))
+}
+/// At least count true is at most the rest false
+pub(super) fn at_least(literals: &[Expression], count: usize)->Expression{
+    if count > literals.len() {
+        return Expression::constant(false);
+    }
+    let negated: Vec<Expression> = literals.iter().map(|literal|!literal.clone()).collect();
+    at_most(&negated, literals.len() - count)
+}
+pub(super) fn exactly(literals: &[Expression], count: usize)->Expression{
+    at_least(literals, count) & at_most(literals, count)
+}
+
+/**
+    A totalizer, the literals counted in unary
+    outputs[j] is forced true whenever more than j of the literals are,
+    so adding !outputs[k] as a fact means at most k of them can be true.
+
+    Built as a tree, each node's outputs count its two halves:
+    if i of the left and j of the right are true then more than i + j - 1 of both are.
+    Only that direction is encoded, which is all an upper bound needs.
+    New variables come from fresh and the facts go into facts.
+*/
+pub(super) fn totalizer(literals: &[KnowledgeBaseLiteral], fresh: &mut impl FnMut()->Variable, facts: &mut Vec<KnowledgeBaseFact>)->Vec<KnowledgeBaseLiteral>{
+    if literals.len() <= 1 {
+        return literals.to_vec();
+    }
+    let (left, right) = literals.split_at(literals.len() / 2);
+    let left = totalizer(left, fresh, facts);
+    let right = totalizer(right, fresh, facts);
+    let outputs: Vec<KnowledgeBaseLiteral> = (0..left.len() + right.len()).map(|_|KnowledgeBaseLiteral::new(false, fresh())).collect();
+    //i and j are how many are true on each side, 0 needs no literal
+    for i in 0..=left.len() {
+        for j in 0..=right.len() {
+            if i + j == 0 {
+                continue;
+            }
+            let mut literals = vec![outputs[i + j - 1].clone()];
+            if i > 0 {
+                literals.push(left[i - 1].negated());
+            }
+            if j > 0 {
+                literals.push(right[j - 1].negated());
+            }
+            facts.push(KnowledgeBaseFact::new(literals));
+        }
+    }
+    outputs
+}
+
+/// At most count of the literals are true as facts, a totalizer grows with the square of the literals instead
+pub(super) fn at_most_facts(literals: &[KnowledgeBaseLiteral], count: usize, fresh: &mut impl FnMut()->Variable, facts: &mut Vec<KnowledgeBaseFact>){
+    if count >= literals.len() {
+        return;
+    }
+    let outputs = totalizer(literals, fresh, facts);
+    facts.push(KnowledgeBaseFact::new(vec![outputs[count].negated()]));
+}
+/// At least count true is at most the rest false
+pub(super) fn at_least_facts(literals: &[KnowledgeBaseLiteral], count: usize, fresh: &mut impl FnMut()->Variable, facts: &mut Vec<KnowledgeBaseFact>){
+    if count > literals.len() {
+        facts.push(KnowledgeBaseFact::new(Vec::new()));
+        return;
+    }
+    let negated: Vec<KnowledgeBaseLiteral> = literals.iter().map(|literal|literal.negated()).collect();
+    at_most_facts(&negated, literals.len() - count, fresh, facts);
+}
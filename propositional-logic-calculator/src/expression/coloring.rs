@@ -0,0 +1,155 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{knowledge_base::{KnowledgeBaseFact, KnowledgeBaseLiteral}, save::LoadError, solver::Solver, variable::Variable, KnowledgeBase};
+
+/**
+    An undirected graph to color, like meetings that can't share a timeslot
+
+    Two formats are read, picked by whether there is a "p edge" line.
+    An edge list has one edge per line as two names, a name alone is a vertex with no edges,
+    and # starts a comment.
+
+    anna_meeting josh_meeting
+    anna_meeting standup
+    lunch
+
+    DIMACS has "p edge <vertices> <edges>", then "e <u> <v>" for every edge counted from 1,
+    c starts a comment. The vertices are named by their number.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct Graph{
+    vertices: Vec<String>,
+    edges: Vec<(usize, usize)>
+}
+impl Graph{
+    pub fn parse(text: &str)->Result<Self, LoadError>{
+        let dimacs = text.lines().any(|line|{
+            let mut words = line.split_whitespace();
+            words.next() == Some("p") && matches!(words.next(), Some("edge" | "col"))
+        });
+        let graph = if dimacs {Self::parse_dimacs(text)?} else {Self::parse_edge_list(text)?};
+        Ok(graph.dedup_edges())
+    }
+    pub fn load(path: &str)->Result<Self, LoadError>{
+        Self::parse(&std::fs::read_to_string(path).map_err(LoadError::Io)?)
+    }
+    fn parse_edge_list(text: &str)->Result<Self, LoadError>{
+        let mut graph = Self::default();
+        let mut ids: HashMap<String, usize> = HashMap::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("");
+            let names: Vec<usize> = line.split_whitespace().map(|name|graph.vertex(&mut ids, name)).collect();
+            match names.as_slice() {
+                [] | [_] => {},
+                [u, v] => graph.add_edge(*u, *v),
+                _ => return Err(LoadError::Corrupted{line: i + 1, reason: "an edge is two names".to_string()}),
+            }
+        }
+        Ok(graph)
+    }
+    fn parse_dimacs(text: &str)->Result<Self, LoadError>{
+        let mut graph = Self::default();
+        for (i, line) in text.lines().enumerate() {
+            let corrupted = |reason: &str|LoadError::Corrupted{line: i + 1, reason: reason.to_string()};
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words.as_slice() {
+                [] | ["c", ..] => {},
+                ["p", _, vertices, ..] => {
+                    let Ok(vertices) = vertices.parse::<usize>() else {return Err(corrupted("the vertex count is not a number"))};
+                    graph.vertices = (1..=vertices).map(|v|v.to_string()).collect();
+                },
+                ["e", u, v] => {
+                    let vertex = |word: &str|word.parse::<usize>().ok().filter(|v|(1..=graph.vertices.len()).contains(v));
+                    let (Some(u), Some(v)) = (vertex(u), vertex(v)) else {
+                        return Err(corrupted("an edge has to be between two vertices from 1 to the vertex count"));
+                    };
+                    graph.add_edge(u - 1, v - 1);
+                },
+                _ => return Err(corrupted("expected c, p edge <vertices> <edges> or e <u> <v>")),
+            }
+        }
+        Ok(graph)
+    }
+    fn vertex(&mut self, ids: &mut HashMap<String, usize>, name: &str)->usize{
+        *ids.entry(name.to_string()).or_insert_with(||{
+            self.vertices.push(name.to_string());
+            self.vertices.len() - 1
+        })
+    }
+    /// Edges are kept once whichever way round they were written
+    fn add_edge(&mut self, u: usize, v: usize){
+        self.edges.push((u.min(v), u.max(v)));
+    }
+    fn dedup_edges(mut self)->Self{
+        self.edges.sort();
+        self.edges.dedup();
+        self
+    }
+    pub fn vertices(&self)->&[String]{
+        &self.vertices
+    }
+    pub fn edges(&self)->&[(usize, usize)]{
+        &self.edges
+    }
+
+    /// True when vertex has color, both counted from 0
+    fn literal(colors: usize, vertex: usize, color: usize, not: bool)->KnowledgeBaseLiteral{
+        KnowledgeBaseLiteral::new(not, Variable::new((vertex * colors + color) as u32))
+    }
+    /**
+        Variable vertex * colors + color is true when vertex has color
+        Every vertex has at least one color and at most one,
+        and the two ends of an edge never have the same one
+    */
+    pub fn knowledge_base(&self, colors: usize)->KnowledgeBase{
+        let literal = |vertex, color, not|Self::literal(colors, vertex, color, not);
+        let mut facts = Vec::new();
+        for vertex in 0..self.vertices.len() {
+            facts.push(KnowledgeBaseFact::new((0..colors).map(|color|literal(vertex, color, false)).collect()));
+            for color in 0..colors {
+                for other in color + 1..colors {
+                    facts.push(KnowledgeBaseFact::new(vec![literal(vertex, color, true), literal(vertex, other, true)]));
+                }
+            }
+        }
+        for (u, v) in self.edges.iter() {
+            for color in 0..colors {
+                facts.push(KnowledgeBaseFact::new(vec![literal(*u, color, true), literal(*v, color, true)]));
+            }
+        }
+        KnowledgeBase::from_facts(facts)
+    }
+    /// The color of every vertex counted from 0, None if colors isn't enough
+    pub fn color(&self, colors: usize)->Option<Vec<usize>>{
+        let model = Solver::new(&self.knowledge_base(colors)).solve()?;
+        Some((0..self.vertices.len()).map(|vertex|{
+            (0..colors)
+                .find(|color|model.value(Self::literal(colors, vertex, *color, false).var()) == Some(true))
+                .expect("every vertex has a color")
+        }).collect())
+    }
+    /**
+        Tries 1 color, then 2 and so on, everything before the answer was proved too few
+        None if a vertex is next to itself, nothing can color that
+    */
+    pub fn fewest_colors(&self)->Option<Vec<usize>>{
+        if self.edges.iter().any(|(u, v)|u == v) {
+            return None;
+        }
+        if self.vertices.is_empty() {
+            return Some(Vec::new());
+        }
+        //one color per vertex always works
+        (1..=self.vertices.len()).find_map(|colors|self.color(colors))
+    }
+    /// One line per vertex with its color counted from 1
+    pub fn display_coloring(&self, coloring: &[usize])->String{
+        let mut out = String::new();
+        for (name, color) in self.vertices.iter().zip(coloring) {
+            out.push_str(&format!("{}: {}\n", name, color + 1));
+        }
+        let used: HashSet<&usize> = coloring.iter().collect();
+        out.push_str(&format!("{} colors\n", used.len()));
+        out
+    }
+}
@@ -0,0 +1,380 @@
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use super::{expression::ExpressionNode, knowledge_base::{KnowledgeBaseFact, KnowledgeBaseLiteral}, variable::Variable};
+
+/// An edge into the dag, the lowest bit says if it is negated
+/// Two literals are equal exactly when they point at the same node with the same sign
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(super) struct DagLiteral(u32);
+impl DagLiteral{
+    pub(super) const FALSE: Self = Self(0);
+    pub(super) const TRUE: Self = Self(1);
+
+    pub(super) fn positive(node: usize)->Self{
+        Self((node as u32) << 1)
+    }
+    pub(super) fn node(&self)->usize{
+        (self.0 >> 1) as usize
+    }
+    pub(super) fn negated(&self)->bool{
+        self.0 & 1 == 1
+    }
+    pub(super) fn not(self)->Self{
+        Self(self.0 ^ 1)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(super) enum DagNode{
+    False,
+    Variable(Variable),
+    And(DagLiteral, DagLiteral)
+}
+
+/**
+    And inverter graph with every node stored once
+
+    Nodes live in an arena and are only ever made through the table,
+    so two equal subterms always end up as the same node and are shared instead of copied.
+    Every operator is built out of And and negated edges,
+    a | b is !(!a & !b) so xor and iff no longer copy their operands.
+
+    And is normalized so a & b and b & a are the same node,
+    and a & a, a & !a, a & true and a & false are folded away.
+*/
+#[derive(Debug, Default)]
+pub(super) struct ExpressionDag{
+    nodes: Vec<DagNode>,
+    table: HashMap<DagNode, usize>
+}
+impl ExpressionDag{
+    pub(super) fn new()->Self{
+        let mut dag = Self::default();
+        dag.intern(DagNode::False);
+        dag
+    }
+    fn intern(&mut self, node: DagNode)->DagLiteral{
+        if let Some(index) = self.table.get(&node) {
+            return DagLiteral::positive(*index);
+        }
+        let index = self.nodes.len();
+        self.nodes.push(node.clone());
+        self.table.insert(node, index);
+        DagLiteral::positive(index)
+    }
+    pub(super) fn node(&self, literal: DagLiteral)->&DagNode{
+        &self.nodes[literal.node()]
+    }
+    pub(super) fn len(&self)->usize{
+        self.nodes.len()
+    }
+    /// One past the highest variable in the dag
+    pub(super) fn first_unused(&self)->u32{
+        self.nodes
+            .iter()
+            .filter_map(|node|match node {
+                DagNode::Variable(var) => Some(var.index() as u32 + 1),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub(super) fn variable(&mut self, var: Variable)->DagLiteral{
+        self.intern(DagNode::Variable(var))
+    }
+    pub(super) fn and(&mut self, a: DagLiteral, b: DagLiteral)->DagLiteral{
+        let (a, b) = if a <= b {(a, b)} else {(b, a)};
+        if a == DagLiteral::FALSE || a == b.not() {
+            return DagLiteral::FALSE;
+        }
+        if a == DagLiteral::TRUE || a == b {
+            return b;
+        }
+        self.intern(DagNode::And(a, b))
+    }
+    pub(super) fn or(&mut self, a: DagLiteral, b: DagLiteral)->DagLiteral{
+        self.and(a.not(), b.not()).not()
+    }
+    pub(super) fn xor(&mut self, a: DagLiteral, b: DagLiteral)->DagLiteral{
+        let left = self.and(a, b.not());
+        let right = self.and(a.not(), b);
+        self.or(left, right)
+    }
+    pub(super) fn implies(&mut self, a: DagLiteral, b: DagLiteral)->DagLiteral{
+        self.or(a.not(), b)
+    }
+    pub(super) fn iff(&mut self, a: DagLiteral, b: DagLiteral)->DagLiteral{
+        self.xor(a, b).not()
+    }
+
+    /// Adds an expression tree, reusing every node that is already in the dag
+    pub(super) fn add(&mut self, node: &ExpressionNode)->DagLiteral{
+        match node {
+            ExpressionNode::Variable(var) => self.variable(*var),
+            ExpressionNode::Constant(true) => DagLiteral::TRUE,
+            ExpressionNode::Constant(false) => DagLiteral::FALSE,
+            ExpressionNode::Not(a) => self.add(a).not(),
+            ExpressionNode::And(a, b) => {let (a, b) = (self.add(a), self.add(b)); self.and(a, b)},
+            ExpressionNode::Or(a, b) => {let (a, b) = (self.add(a), self.add(b)); self.or(a, b)},
+            ExpressionNode::Xor(a, b) => {let (a, b) = (self.add(a), self.add(b)); self.xor(a, b)},
+            ExpressionNode::Implies(a, b) => {let (a, b) = (self.add(a), self.add(b)); self.implies(a, b)},
+            ExpressionNode::Iff(a, b) => {let (a, b) = (self.add(a), self.add(b)); self.iff(a, b)},
+            ExpressionNode::Nand(a, b) => {let (a, b) = (self.add(a), self.add(b)); self.and(a, b).not()},
+            ExpressionNode::Nor(a, b) => {let (a, b) = (self.add(a), self.add(b)); self.or(a, b).not()},
+        }
+    }
+}
+
+/// Distributing an or that would make more facts than this gives it a fresh variable instead, when defining
+const MAX_DISTRIBUTED: usize = 64;
+
+/**
+    Turns a dag literal into facts by distributing or over and
+
+    The facts of every literal are remembered,
+    so a subterm that is used many times is only converted once.
+    a & b is the facts of a plus the facts of b
+    !(a & b) is !a | !b, every fact of !a joined with every fact of !b
+
+    Distributing can still double the facts at every step of something like a chain of iffs.
+    A builder made with defining stops that by giving some negated ands a fresh variable d instead, Tseitin style.
+    A negated and gets one when its node is used by more than one and,
+    or when distributing it would make more than MAX_DISTRIBUTED facts.
+    Every other negated and is distributed like above.
+    d <> a & b is [!d, a], [!d, b], [d, !a, !b], with a and b defined the same way if they are ands,
+    so every model of the expression has exactly one value for each fresh variable.
+*/
+pub(super) struct DagCnfBuilder<'a>{
+    dag: &'a ExpressionDag,
+    cache: HashMap<DagLiteral, Rc<Vec<KnowledgeBaseFact>>>,
+    definitions: Option<Definitions>
+}
+struct Definitions{
+    //how many ands use each node
+    parents: Vec<usize>,
+    variables: HashMap<usize, Variable>,
+    next_fresh: u32,
+    facts: Vec<KnowledgeBaseFact>
+}
+impl<'a> DagCnfBuilder<'a>{
+    pub(super) fn new(dag: &'a ExpressionDag)->Self{
+        Self{dag, cache: HashMap::new(), definitions: None}
+    }
+    /// Fresh variables start at first_fresh, nothing under root can use them
+    pub(super) fn defining(dag: &'a ExpressionDag, root: DagLiteral, first_fresh: u32)->Self{
+        let mut parents = vec![0; dag.len()];
+        let mut visited = HashSet::new();
+        let mut stack = vec![root.node()];
+        while let Some(node) = stack.pop() {
+            if !visited.insert(node) {
+                continue;
+            }
+            if let DagNode::And(a, b) = dag.nodes[node] {
+                for child in [a.node(), b.node()] {
+                    parents[child] += 1;
+                    stack.push(child);
+                }
+            }
+        }
+        let definitions = Definitions{parents, variables: HashMap::new(), next_fresh: first_fresh, facts: Vec::new()};
+        Self{dag, cache: HashMap::new(), definitions: Some(definitions)}
+    }
+    /// The facts defining every fresh variable so far and the first variable not used yet
+    pub(super) fn definitions(self)->(Vec<KnowledgeBaseFact>, u32){
+        let Some(definitions) = self.definitions else {unreachable!("made with defining")};
+        (definitions.facts, definitions.next_fresh)
+    }
+    pub(super) fn facts(&mut self, literal: DagLiteral)->Rc<Vec<KnowledgeBaseFact>>{
+        if let Some(facts) = self.cache.get(&literal) {
+            return facts.clone();
+        }
+
+        let facts = match (self.dag.node(literal), literal.negated()) {
+            (DagNode::False, false) => vec![KnowledgeBaseFact::new(Vec::new())],
+            (DagNode::False, true) => Vec::new(),
+            (DagNode::Variable(var), not) => vec![KnowledgeBaseFact::new(vec![KnowledgeBaseLiteral::new(not, *var)])],
+            //a long chain of ands would copy everything so far once per link, so every conjunct is taken at once
+            (DagNode::And(_, _), false) => {
+                let mut facts = Vec::new();
+                let mut visited = HashSet::new();
+                let mut stack = vec![literal];
+                while let Some(next) = stack.pop() {
+                    if !visited.insert(next) {
+                        continue;
+                    }
+                    match (self.dag.node(next), next.negated()) {
+                        (DagNode::And(a, b), false) => {
+                            stack.push(*b);
+                            stack.push(*a);
+                        },
+                        _ => facts.extend(self.facts(next).iter().cloned()),
+                    }
+                }
+                Self::dedup(facts)
+            },
+            (DagNode::And(_, _), true) if self.shared(literal.node()) => self.defined_fact(literal),
+            (DagNode::And(a, b), true) => {
+                let (a, b) = (a.not(), b.not());
+                let a_facts = self.facts(a);
+                let b_facts = self.facts(b);
+                if self.definitions.is_some() && a_facts.len() * b_facts.len() > MAX_DISTRIBUTED {
+                    self.defined_fact(literal)
+                }else{
+                    let mut facts = Vec::with_capacity(a_facts.len() * b_facts.len());
+                    for a_fact in a_facts.iter() {
+                        for b_fact in b_facts.iter() {
+                            let joined = KnowledgeBaseFact::new(a_fact.literals().iter().chain(b_fact.literals()).cloned().collect());
+                            if !joined.tautology() {
+                                facts.push(joined);
+                            }
+                        }
+                    }
+                    Self::dedup(facts)
+                }
+            },
+        };
+
+        let facts = Rc::new(facts);
+        self.cache.insert(literal, facts.clone());
+        facts
+    }
+    fn dedup(facts: Vec<KnowledgeBaseFact>)->Vec<KnowledgeBaseFact>{
+        let mut seen = HashSet::new();
+        facts.into_iter().filter(|fact|seen.insert(fact.clone())).collect()
+    }
+
+    fn shared(&self, node: usize)->bool{
+        self.definitions.as_ref().is_some_and(|definitions|definitions.parents[node] > 1)
+    }
+    fn defined_fact(&mut self, literal: DagLiteral)->Vec<KnowledgeBaseFact>{
+        vec![KnowledgeBaseFact::new(vec![self.literal(literal)])]
+    }
+    /// A variable is itself and an and is its fresh variable, defining it the first time
+    fn literal(&mut self, literal: DagLiteral)->KnowledgeBaseLiteral{
+        let var = match self.dag.node(literal) {
+            DagNode::Variable(var) => *var,
+            DagNode::And(a, b) => self.define(literal.node(), *a, *b),
+            //and folds constants away so they are never under one
+            DagNode::False => unreachable!("constants are folded"),
+        };
+        KnowledgeBaseLiteral::new(literal.negated(), var)
+    }
+    fn define(&mut self, node: usize, a: DagLiteral, b: DagLiteral)->Variable{
+        let Some(definitions) = &self.definitions else {unreachable!("only called when defining")};
+        if let Some(var) = definitions.variables.get(&node) {
+            return *var;
+        }
+        let (a, b) = (self.literal(a), self.literal(b));
+        let Some(definitions) = &mut self.definitions else {unreachable!("only called when defining")};
+        let var = Variable::new(definitions.next_fresh);
+        definitions.next_fresh += 1;
+        definitions.variables.insert(node, var);
+
+        let (positive, negative) = (KnowledgeBaseLiteral::new(false, var), KnowledgeBaseLiteral::new(true, var));
+        definitions.facts.push(KnowledgeBaseFact::new(vec![negative.clone(), a.clone()]));
+        definitions.facts.push(KnowledgeBaseFact::new(vec![negative, b.clone()]));
+        definitions.facts.push(KnowledgeBaseFact::new(vec![positive, a.negated(), b.negated()]));
+        var
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::super::{model_counter::ModelCounter, solver::Solver, test_helpers::{holds, node_holds, random_node, Random}, Expression, KnowledgeBase, Workspace};
+    use super::*;
+
+    /// The dag gives facts with the same models as rewriting the tree
+    #[test]
+    fn matches_the_tree(){
+        let mut random = Random(0xB5026F5AA96619E9);
+        for _ in 0..1000 {
+            let expression = Expression::from_node(random_node(&mut random, 4));
+            let dag = KnowledgeBase::from_expression(expression.clone());
+            let tree = KnowledgeBase::from_expression_tree(expression.clone());
+            for assignment in 0..1 << 5 {
+                assert_eq!(holds(dag.facts(), assignment), node_holds(expression.node(), assignment), "{:?}", expression);
+                assert_eq!(holds(dag.facts(), assignment), holds(tree.facts(), assignment), "{:?}", expression);
+            }
+        }
+    }
+
+    /// Every model of the expression has exactly one value for the fresh variables and nothing else has any
+    #[test]
+    fn definitions_extend_models_once(){
+        let mut random = Random(0x4F1BBCDCBFA53E0B);
+        for _ in 0..300 {
+            let expression = Expression::from_node(random_node(&mut random, 5));
+            let (kb, next_fresh) = KnowledgeBase::from_expression_defined(expression.clone(), 5);
+            let fresh: Vec<Variable> = (5..next_fresh).map(Variable::new).collect();
+            assert!(kb.facts().iter().flat_map(|fact|fact.literals()).all(|literal|literal.var().index() < next_fresh as usize));
+
+            for assignment in 0..1u32 << 5 {
+                let mut solver = Solver::new(&kb);
+                for var in 0..5 {
+                    solver.add_fact(&KnowledgeBaseFact::new(vec![KnowledgeBaseLiteral::new(assignment >> var & 1 == 0, Variable::new(var))]));
+                }
+                let model = solver.solve();
+                assert_eq!(model.is_some(), node_holds(expression.node(), assignment), "{:?}", expression);
+                let Some(model) = model else {continue};
+                //rule out the values it found, there must be no others
+                let other = fresh.iter().filter_map(|var|model.value(var).map(|value|KnowledgeBaseLiteral::new(value, *var))).collect();
+                solver.add_fact(&KnowledgeBaseFact::new(other));
+                assert!(fresh.is_empty() || solver.solve().is_none(), "{:?}", expression);
+            }
+        }
+    }
+
+    /// A chain of iffs gets fresh variables instead of doubling
+    #[test]
+    fn defining_keeps_chains_small(){
+        let node = (1..64).fold(ExpressionNode::new_variable(Variable::new(0)), |chain, i|{
+            ExpressionNode::new_biconditional(chain, ExpressionNode::new_variable(Variable::new(i)))
+        });
+        let (kb, next_fresh) = KnowledgeBase::from_expression_defined(Expression::from_node(node), 0);
+        assert!(next_fresh > 64);
+        assert!(kb.facts().len() < 1000);
+    }
+
+    /// Chains typed into the workspace get fresh variables, which stay out of models and don't change probabilities
+    #[test]
+    fn workspace_keeps_chains_small(){
+        let mut workspace = Workspace::new();
+        let xors: Vec<String> = (0..40).map(|i|format!("x{}", i)).collect();
+        let iffs: Vec<String> = (0..40).map(|i|format!("y{}", i)).collect();
+        workspace.parse_line(&xors.join(" ^ ")).unwrap();
+        workspace.parse_line(&iffs.join(" <> ")).unwrap();
+
+        let kb = workspace.expression_set.knowledge_base();
+        assert!(kb.facts().len() < 2000, "{} facts", kb.facts().len());
+        let fresh = workspace.fresh_variables(&kb);
+        assert!(!fresh.is_empty());
+        assert!(fresh.iter().all(|var|workspace.variable_names.name(var).is_none()));
+        assert_eq!(workspace.variable_names.variables().len(), 80);
+
+        let model = workspace.solve().unwrap();
+        assert!(fresh.iter().all(|var|model.value(var).is_none()));
+        let value = |name: &String|model.value(&workspace.variable_names.names[name]).unwrap();
+        assert_eq!(xors.iter().filter(|name|value(name)).count() % 2, 1);
+        //a chain of iffs over an even number holds when an even number are false
+        assert_eq!(iffs.iter().filter(|name|!value(name)).count() % 2, 0);
+
+        //odd is (1 - (1 - 2p)^4) / 2 for four of probability p
+        let mut workspace = Workspace::new();
+        for line in ["a ^ b ^ c ^ d", "p(a) = 0.9", "p(b) = 0.9", "p(c) = 0.9", "p(d) = 0.9"] {
+            workspace.parse_line(line).unwrap();
+        }
+        let kb = workspace.expression_set.knowledge_base();
+        let mut weights = workspace.weights.clone();
+        for var in workspace.fresh_variables(&kb) {
+            weights.set_auxiliary(var);
+        }
+        assert!((ModelCounter::new(&weights).count(&kb) - (1.0 - 0.8f64.powi(4)) / 2.0).abs() < 1e-12);
+
+        //forgetting them gives back every fact distributing would have
+        let named = workspace.named_knowledge_base();
+        assert!(workspace.fresh_variables(&named).is_empty());
+        assert_eq!(named.facts().len(), 8);
+    }
+}
@@ -0,0 +1,52 @@
+use std::fmt;
+
+use super::{knowledge_base::{KnowledgeBaseFact, KnowledgeBaseLiteral}, printer::{ExpressionPrinter, OperatorStyle}, solver::Model, Expression, KnowledgeBase, VariableNames};
+
+/// Something shown with the names of its variables
+/// Made by with_names on an expression, knowledge base, fact, literal or model
+pub struct Named<'a, T>{
+    value: &'a T,
+    names: &'a VariableNames
+}
+
+macro_rules! with_names {
+    ($($t:ty),*) => {$(
+        impl $t{
+            pub fn with_names<'a>(&'a self, names: &'a VariableNames)->Named<'a, Self>{
+                Named{value: self, names}
+            }
+        }
+    )*};
+}
+with_names!(Expression, KnowledgeBase, KnowledgeBaseFact, KnowledgeBaseLiteral, Model);
+
+/// a & (b | !c)
+impl fmt::Display for Named<'_, Expression>{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>)->fmt::Result{
+        write!(f, "{}", ExpressionPrinter::new(self.names, OperatorStyle::Ascii).print(self.value.node()))
+    }
+}
+/// One fact per line
+impl fmt::Display for Named<'_, KnowledgeBase>{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>)->fmt::Result{
+        write!(f, "{}", self.value.display(self.names))
+    }
+}
+/// [a, !b]
+impl fmt::Display for Named<'_, KnowledgeBaseFact>{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>)->fmt::Result{
+        write!(f, "{}", self.names.display_fact(self.value))
+    }
+}
+/// !a
+impl fmt::Display for Named<'_, KnowledgeBaseLiteral>{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>)->fmt::Result{
+        write!(f, "{}", self.names.display_literal(self.value))
+    }
+}
+/// One name: true|false|free line per variable
+impl fmt::Display for Named<'_, Model>{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>)->fmt::Result{
+        write!(f, "{}", self.value.display(self.names))
+    }
+}
@@ -0,0 +1,186 @@
+use std::collections::HashSet;
+
+use super::{dag::{DagLiteral, DagNode, ExpressionDag}, expression::ExpressionNode, parser::ExpressionParserToken, printer::OperatorStyle, KnowledgeBase, VariableNames};
+
+//graphviz output, paste into any dot viewer or run dot -Tsvg
+
+/// Each node of every tree is its own dot node, so repeated subterms show up again every time
+pub(super) fn expression_trees_dot(nodes: &[&ExpressionNode], vars: &VariableNames, style: OperatorStyle)->String{
+    let mut out = String::from("digraph expressions {\n");
+    let mut next_id = 0;
+    for node in nodes {
+        tree_dot_recursive(node, vars, style, &mut next_id, &mut out);
+    }
+    out.push_str("}\n");
+    out
+}
+/// Returns the id of the dot node made for node
+fn tree_dot_recursive(node: &ExpressionNode, vars: &VariableNames, style: OperatorStyle, next_id: &mut usize, out: &mut String)->usize{
+    let id = *next_id;
+    *next_id += 1;
+
+    let (label, children): (String, Vec<&ExpressionNode>) = match node {
+        ExpressionNode::Variable(var) => (vars.display_variable(var), vec![]),
+        ExpressionNode::Constant(value) => (style.symbol(&ExpressionParserToken::Constant(*value)).to_string(), vec![]),
+        ExpressionNode::Not(a) => (style.symbol(&ExpressionParserToken::Not).trim().to_string(), vec![a]),
+        ExpressionNode::And(a, b) => (style.symbol(&ExpressionParserToken::And).to_string(), vec![a, b]),
+        ExpressionNode::Or(a, b) => (style.symbol(&ExpressionParserToken::Or).to_string(), vec![a, b]),
+        ExpressionNode::Xor(a, b) => (style.symbol(&ExpressionParserToken::Xor).to_string(), vec![a, b]),
+        ExpressionNode::Implies(a, b) => (style.symbol(&ExpressionParserToken::ImpliesRight).to_string(), vec![a, b]),
+        ExpressionNode::Iff(a, b) => (style.symbol(&ExpressionParserToken::Biconditional).to_string(), vec![a, b]),
+        ExpressionNode::Nand(a, b) => (style.symbol(&ExpressionParserToken::Nand).to_string(), vec![a, b]),
+        ExpressionNode::Nor(a, b) => (style.symbol(&ExpressionParserToken::Nor).to_string(), vec![a, b]),
+    };
+    let shape = if children.is_empty() {"box"} else {"ellipse"};
+    out.push_str(&format!("    n{} [label=\"{}\", shape={}];\n", id, escape(&label), shape));
+
+    //left to right in the order they were written
+    for child in children {
+        let child_id = tree_dot_recursive(child, vars, style, next_id, out);
+        out.push_str(&format!("    n{} -> n{};\n", id, child_id));
+    }
+    id
+}
+
+/**
+    Only the nodes reachable from the roots, each drawn once however often it is used
+    Negated edges are dashed with a circle at the end
+    Every root gets a node of its own, numbered in the order the expressions were written
+*/
+pub(super) fn dag_dot(dag: &ExpressionDag, roots: &[DagLiteral], vars: &VariableNames, style: OperatorStyle)->String{
+    let mut out = String::from("digraph dag {\n");
+    let mut seen = HashSet::new();
+    let mut stack = Vec::new();
+
+    for (i, root) in roots.iter().enumerate() {
+        out.push_str(&format!("    e{} [label=\"{}\", shape=plaintext];\n", i, i + 1));
+        out.push_str(&format!("    e{} -> d{}{};\n", i, root.node(), edge_style(*root)));
+        stack.push(root.node());
+    }
+
+    while let Some(index) = stack.pop() {
+        if !seen.insert(index) {
+            continue;
+        }
+        let literal = DagLiteral::positive(index);
+        match dag.node(literal) {
+            DagNode::False => out.push_str(&format!("    d{} [label=\"{}\", shape=box];\n", index, escape(style.symbol(&ExpressionParserToken::Constant(false))))),
+            DagNode::Variable(var) => out.push_str(&format!("    d{} [label=\"{}\", shape=box];\n", index, escape(&vars.display_variable(var)))),
+            DagNode::And(a, b) => {
+                out.push_str(&format!("    d{} [label=\"{}\"];\n", index, escape(style.symbol(&ExpressionParserToken::And))));
+                for child in [a, b] {
+                    out.push_str(&format!("    d{} -> d{}{};\n", index, child.node(), edge_style(*child)));
+                    stack.push(child.node());
+                }
+            },
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+fn edge_style(literal: DagLiteral)->&'static str{
+    if literal.negated() {" [style=dashed, arrowhead=odot]"} else {""}
+}
+
+/**
+    Facts on one side, variables on the other, an edge where a variable is in a fact
+    Green edges are positive literals, red edges are negated ones
+    Facts are kept in one column, labelled with their literals
+*/
+pub(super) fn knowledge_base_dot(kb: &KnowledgeBase, vars: &VariableNames)->String{
+    let mut out = String::from("graph knowledge_base {\n    rankdir=LR;\n");
+    let mut variables = HashSet::new();
+
+    out.push_str("    subgraph facts {\n        rank=same;\n");
+    for (i, fact) in kb.facts().iter().enumerate() {
+        out.push_str(&format!("        f{} [label=\"{}\", shape=box];\n", i, escape(&vars.display_fact(fact))));
+    }
+    out.push_str("    }\n");
+
+    for (i, fact) in kb.facts().iter().enumerate() {
+        for literal in fact.literals() {
+            if variables.insert(*literal.var()) {
+                out.push_str(&format!("    v{} [label=\"{}\"];\n", literal.var().index(), escape(&vars.display_variable(literal.var()))));
+            }
+            let color = if literal.not() {"red"} else {"forestgreen"};
+            out.push_str(&format!("    f{} -- v{} [color={}];\n", i, literal.var().index(), color));
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn escape(label: &str)->String{
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::Expression;
+
+    /// Latex symbols have backslashes, which dot needs doubled
+    #[test]
+    fn tree_snapshot(){
+        let mut vars = VariableNames::new();
+        let expression = Expression::parse("a & (b | !c)", &mut vars).unwrap();
+        assert_eq!(expression_trees_dot(&[expression.node()], &vars, OperatorStyle::Latex), r#"digraph expressions {
+    n0 [label="\\land", shape=ellipse];
+    n1 [label="a", shape=box];
+    n0 -> n1;
+    n2 [label="\\lor", shape=ellipse];
+    n3 [label="b", shape=box];
+    n2 -> n3;
+    n4 [label="\\lnot", shape=ellipse];
+    n5 [label="c", shape=box];
+    n4 -> n5;
+    n2 -> n4;
+    n0 -> n2;
+}
+"#);
+    }
+
+    /// a | !b is !(!a & b), an and with dashed edges where it is negated
+    #[test]
+    fn dag_snapshot(){
+        let mut vars = VariableNames::new();
+        let mut dag = ExpressionDag::new();
+        let root = dag.add(Expression::parse("a | !b", &mut vars).unwrap().node());
+        assert_eq!(dag_dot(&dag, &[root], &vars, OperatorStyle::Ascii), r#"digraph dag {
+    e0 [label="1", shape=plaintext];
+    e0 -> d3 [style=dashed, arrowhead=odot];
+    d3 [label="&"];
+    d3 -> d1 [style=dashed, arrowhead=odot];
+    d3 -> d2;
+    d2 [label="b", shape=box];
+    d1 [label="a", shape=box];
+}
+"#);
+    }
+
+    #[test]
+    fn knowledge_base_snapshot(){
+        let mut vars = VariableNames::new();
+        let expression = Expression::parse("a & (b | !c)", &mut vars).unwrap();
+        assert_eq!(knowledge_base_dot(&KnowledgeBase::from_expression(expression), &vars), r#"graph knowledge_base {
+    rankdir=LR;
+    subgraph facts {
+        rank=same;
+        f0 [label="[a]", shape=box];
+        f1 [label="[b, !c]", shape=box];
+    }
+    v0 [label="a"];
+    f0 -- v0 [color=forestgreen];
+    v1 [label="b"];
+    f1 -- v1 [color=forestgreen];
+    v2 [label="c"];
+    f1 -- v2 [color=red];
+}
+"#);
+    }
+
+    #[test]
+    fn escapes_quotes_and_backslashes(){
+        assert_eq!(escape(r#"say "hi" \ bye"#), r#"say \"hi\" \\ bye"#);
+    }
+}
@@ -0,0 +1,221 @@
+use std::fmt;
+
+use super::{builder::Var, cardinality::{at_most, exactly}, expression::Expression, save::LoadError, solver::Model, Workspace};
+
+/// Every var as an expression
+fn literals(vars: &[Var])->Vec<Expression>{
+    vars.iter().map(|var|Expression::from(*var)).collect()
+}
+
+/**
+    A 9 by 9 sudoku, 0 is an empty cell
+
+    x[r][c][v] is true when row r column c is v, all counted from 1.
+    Every cell, row, column and box has exactly one of each value,
+    each of those is one labelled expression so :print shows where a fact came from.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sudoku{
+    cells: [[u8; 9]; 9]
+}
+impl Sudoku{
+    /**
+        One row per line, 1 to 9 for a given and . or 0 for an empty cell
+        Spaces and | - + are left out, so a board printed by this can be read back
+    */
+    pub fn parse(text: &str)->Result<Self, LoadError>{
+        let mut cells = [[0; 9]; 9];
+        let mut row = 0;
+        for (i, line) in text.lines().enumerate() {
+            let corrupted = |reason: String|LoadError::Corrupted{line: i + 1, reason};
+            let line: Vec<char> = line.chars().filter(|c|!c.is_whitespace() && !matches!(c, '|' | '-' | '+')).collect();
+            if line.is_empty() {
+                continue;
+            }
+            if row == 9 {
+                return Err(corrupted("there are more than 9 rows".to_string()));
+            }
+            if line.len() != 9 {
+                return Err(corrupted(format!("a row has 9 cells, not {}", line.len())));
+            }
+            for (column, c) in line.iter().enumerate() {
+                cells[row][column] = match c {
+                    '.' => 0,
+                    '0'..='9' => *c as u8 - b'0',
+                    _ => return Err(corrupted(format!("{} is not a digit or .", c))),
+                };
+            }
+            row += 1;
+        }
+        if row != 9 {
+            return Err(LoadError::Corrupted{line: text.lines().count(), reason: format!("there are {} rows, not 9", row)});
+        }
+        Ok(Self{cells})
+    }
+    pub fn load(path: &str)->Result<Self, LoadError>{
+        Self::parse(&std::fs::read_to_string(path).map_err(LoadError::Io)?)
+    }
+    /// 0 if the cell is empty, rows and columns count from 0
+    pub fn cell(&self, row: usize, column: usize)->u8{
+        self.cells[row][column]
+    }
+
+    pub fn workspace(&self)->Workspace{
+        self.encode().0
+    }
+    /// Every cell filled in, None if there is no way to
+    pub fn solve(&self)->Option<Self>{
+        let (mut workspace, vars) = self.encode();
+        let model = workspace.solve()?;
+        Some(self.decode(&vars, &model))
+    }
+    /// vars[r][c][v] with everything counted from 0
+    fn encode(&self)->(Workspace, Vec<Vec<Vec<Var>>>){
+        let mut workspace = Workspace::new();
+        let vars: Vec<Vec<Vec<Var>>> = (1..=9).map(|r|(1..=9).map(|c|(1..=9).map(|v|{
+            workspace.var(&format!("x[{}][{}][{}]", r, c, v)).expect("indexed names parse")
+        }).collect()).collect()).collect();
+
+        let mut add = |label: String, literals: Vec<Expression>|{
+            workspace.add_expression(Some(&label), exactly(&literals, 1)).expect("labels are only used once");
+        };
+        for (r, row) in vars.iter().enumerate() {
+            for (c, cell) in row.iter().enumerate() {
+                add(format!("cell_{}_{}", r + 1, c + 1), literals(cell));
+            }
+        }
+        for i in 0..9 {
+            let (top, left) = (i / 3 * 3, i % 3 * 3);
+            let groups: [(&str, Vec<(usize, usize)>); 3] = [
+                ("row", (0..9).map(|c|(i, c)).collect()),
+                ("column", (0..9).map(|r|(r, i)).collect()),
+                ("box", (0..9).map(|k|(top + k / 3, left + k % 3)).collect()),
+            ];
+            for (name, cells) in groups {
+                let cells: Vec<&Vec<Var>> = cells.iter().map(|(r, c)|&vars[*r][*c]).collect();
+                for v in 0..9 {
+                    add(format!("{}_{}_has_{}", name, i + 1, v + 1), cells.iter().map(|cell|cell[v].into()).collect());
+                }
+            }
+        }
+        for (r, row) in self.cells.iter().enumerate() {
+            for (c, value) in row.iter().enumerate() {
+                if *value != 0 {
+                    workspace.add_expression(Some(&format!("given_{}_{}", r + 1, c + 1)), vars[r][c][*value as usize - 1].into())
+                        .expect("labels are only used once");
+                }
+            }
+        }
+        (workspace, vars)
+    }
+    fn decode(&self, vars: &[Vec<Vec<Var>>], model: &Model)->Self{
+        let mut solved = self.clone();
+        for (r, row) in vars.iter().enumerate() {
+            for (c, cell) in row.iter().enumerate() {
+                if let Some(v) = cell.iter().position(|var|model.value(&var.variable()) == Some(true)) {
+                    solved.cells[r][c] = v as u8 + 1;
+                }
+            }
+        }
+        solved
+    }
+}
+/// The grid with lines between the boxes, empty cells are .
+impl fmt::Display for Sudoku{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>)->fmt::Result{
+        for (r, row) in self.cells.iter().enumerate() {
+            if r == 3 || r == 6 {
+                writeln!(f, "------+-------+------")?;
+            }
+            let mut line = String::new();
+            for (c, value) in row.iter().enumerate() {
+                if c == 3 || c == 6 {
+                    line.push_str("| ");
+                }
+                line.push(if *value == 0 {'.'} else {(b'0' + value) as char});
+                line.push(' ');
+            }
+            writeln!(f, "{}", line.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+/**
+    size queens on a size by size board where none can take another
+
+    q[r][c] is true when there is a queen at row r column c, counted from 1.
+    Every row has exactly one queen, every column and diagonal at most one.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct Queens{
+    size: usize,
+    //the column of the queen in each row, empty until solved
+    columns: Vec<usize>
+}
+impl Queens{
+    pub fn new(size: usize)->Self{
+        Self{size, columns: Vec::new()}
+    }
+    pub fn size(&self)->usize{
+        self.size
+    }
+    /// The column of the queen in every row counting from 0, empty if it isn't solved
+    pub fn columns(&self)->&[usize]{
+        &self.columns
+    }
+
+    pub fn workspace(&self)->Workspace{
+        self.encode().0
+    }
+    /// None if the queens can't be placed, like on a 3 by 3 board
+    pub fn solve(&self)->Option<Self>{
+        let (mut workspace, vars) = self.encode();
+        let model = workspace.solve()?;
+        let columns = vars
+            .iter()
+            .map(|row|row.iter().position(|var|model.value(&var.variable()) == Some(true)).expect("every row has a queen"))
+            .collect();
+        Some(Self{size: self.size, columns})
+    }
+    /// vars[r][c] counted from 0
+    fn encode(&self)->(Workspace, Vec<Vec<Var>>){
+        let size = self.size;
+        let mut workspace = Workspace::new();
+        let vars: Vec<Vec<Var>> = (1..=size).map(|r|(1..=size).map(|c|{
+            workspace.var(&format!("q[{}][{}]", r, c)).expect("indexed names parse")
+        }).collect()).collect();
+
+        let mut add = |label: String, expression: Expression|{
+            workspace.add_expression(Some(&label), expression).expect("labels are only used once");
+        };
+        for (i, row) in vars.iter().enumerate() {
+            add(format!("row_{}", i + 1), exactly(&literals(row), 1));
+            add(format!("column_{}", i + 1), at_most(&vars.iter().map(|row|row[i].into()).collect::<Vec<_>>(), 1));
+        }
+        //r - c is the same along a diagonal and r + c along an antidiagonal, corners only have one square
+        for d in 0..(2 * size).saturating_sub(1) {
+            let diagonal: Vec<Expression> = (0..size)
+                .filter_map(|r|(r + d).checked_sub(size - 1).filter(|c|*c < size).map(|c|vars[r][c].into()))
+                .collect();
+            let antidiagonal: Vec<Expression> = (0..size)
+                .filter_map(|r|d.checked_sub(r).filter(|c|*c < size).map(|c|vars[r][c].into()))
+                .collect();
+            if diagonal.len() > 1 {
+                add(format!("diagonal_{}", d + 1), at_most(&diagonal, 1));
+                add(format!("antidiagonal_{}", d + 1), at_most(&antidiagonal, 1));
+            }
+        }
+        (workspace, vars)
+    }
+}
+/// . for an empty square and Q for a queen
+impl fmt::Display for Queens{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>)->fmt::Result{
+        for r in 0..self.size {
+            let row: Vec<&str> = (0..self.size).map(|c|if self.columns.get(r) == Some(&c) {"Q"} else {"."}).collect();
+            writeln!(f, "{}", row.join(" "))?;
+        }
+        Ok(())
+    }
+}
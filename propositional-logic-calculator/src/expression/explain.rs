@@ -0,0 +1,115 @@
+use super::{knowledge_base::BuildStep, printer::{ExpressionPrinter, OperatorStyle}, rewrite::RewriteStep, simplifier::SimplifyStep, Expression, KnowledgeBase, VariableNames};
+
+/**
+    Every step of turning expressions into a simplified knowledge base
+
+    Goes the long way through the tree so each rewrite can be shown,
+    lower, then push down nots, then distribute or over and, then build facts from the ors,
+    and then only simplifies without probing.
+    Solving goes through the dag and probes, so its facts can differ from these,
+    but both have exactly the same models.
+    The tree can get very big so it is only for explaining.
+*/
+pub(super) struct Explanation{
+    expressions: Vec<ExpressionExplanation>,
+    simplified: Vec<SimplifyStep>,
+    knowledge_base: KnowledgeBase
+}
+struct ExpressionExplanation{
+    expression: Expression,
+    lowered: Vec<RewriteStep>,
+    pushed_down: Vec<RewriteStep>,
+    distributed: Vec<RewriteStep>,
+    built: Vec<BuildStep>
+}
+impl Explanation{
+    pub(super) fn new(expressions: &[&Expression])->Self{
+        let mut knowledge_base = KnowledgeBase::tautology();
+        let expressions = expressions
+            .iter()
+            .map(|expression|{
+                let (mut lowered, mut pushed_down, mut distributed) = (Vec::new(), Vec::new(), Vec::new());
+                let cnf_node = (*expression).clone().node_owned()
+                    .lower_recursive(Some(&mut lowered))
+                    .pushdown_not_recursive(Some(&mut pushed_down))
+                    .distribute_or_recursive(Some(&mut distributed));
+                let (kb, built) = KnowledgeBase::from_cnf_tree_explained(*cnf_node);
+                knowledge_base.combine(kb);
+                ExpressionExplanation{expression: (*expression).clone(), lowered, pushed_down, distributed, built}
+            })
+            .collect();
+
+        let simplified = knowledge_base.simplify_explained();
+        Self{expressions, simplified, knowledge_base}
+    }
+
+    pub(super) fn display(&self, vars: &VariableNames, style: OperatorStyle)->String{
+        let printer = ExpressionPrinter::new(vars, style);
+        let mut out = String::new();
+
+        for explanation in self.expressions.iter() {
+            out.push_str(&format!("{}\n", printer.print(explanation.expression.node())));
+            for step in explanation.lowered.iter().chain(&explanation.pushed_down).chain(&explanation.distributed) {
+                out.push_str(&format!("  {}: {} becomes {}\n", step.rule, printer.print(&step.before), printer.print(&step.after)));
+            }
+            for step in explanation.built.iter() {
+                match &step.fact {
+                    Some(fact) => out.push_str(&format!("  build: {} becomes {}\n", printer.print(&step.clause), vars.display_fact(fact))),
+                    None => out.push_str(&format!("  build: {} is always true\n", printer.print(&step.clause))),
+                }
+            }
+        }
+
+        out.push_str("simplify\n");
+        for step in self.simplified.iter() {
+            out.push_str(&match step {
+                SimplifyStep::Tautology(fact) => format!("  tautology: {} removed\n", vars.display_fact(fact)),
+                SimplifyStep::Duplicate(fact) => format!("  duplicate: {} removed\n", vars.display_fact(fact)),
+                SimplifyStep::Satisfied{unit, removed} => format!("  unit propagation: {} removes {}\n", vars.display_literal(unit), vars.display_fact(removed)),
+                SimplifyStep::Propagated{unit, before, after} => format!("  unit propagation: {} makes {} into {}\n", vars.display_literal(unit), vars.display_fact(before), vars.display_fact(after)),
+                SimplifyStep::Subsumed{by, removed} => format!("  subsumption: {} removes {}\n", vars.display_fact(by), vars.display_fact(removed)),
+                SimplifyStep::Contradiction => "  contradiction: everything becomes []\n".to_string(),
+            });
+        }
+        out.push_str(&self.knowledge_base.display(vars));
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::{probing::ProbeBudget, test_helpers::{holds, random_node, Random}};
+
+    /// The explained knowledge base has the same models as the dag and probing give when solving
+    #[test]
+    fn same_models_as_the_dag(){
+        let mut random = Random(0x9FB21C651E98DF25);
+        for _ in 0..300 {
+            let expressions = [Expression::from_node(random_node(&mut random, 3)), Expression::from_node(random_node(&mut random, 3))];
+            let explanation = Explanation::new(&[&expressions[0], &expressions[1]]);
+            let mut dag = KnowledgeBase::from_expression(expressions[0].clone());
+            dag.combine(KnowledgeBase::from_expression(expressions[1].clone()));
+            dag.simplify_with_probing(&ProbeBudget::default());
+            for assignment in 0..1 << 5 {
+                assert_eq!(holds(explanation.knowledge_base.facts(), assignment), holds(dag.facts(), assignment), "{:?}", expressions);
+            }
+        }
+    }
+
+    #[test]
+    fn shows_every_stage(){
+        let mut vars = VariableNames::new();
+        let expressions = [Expression::parse("!(a > b)", &mut vars).unwrap(), Expression::parse("a | c", &mut vars).unwrap()];
+        let shown = Explanation::new(&[&expressions[0], &expressions[1]]).display(&vars, OperatorStyle::Ascii);
+        for line in [
+            "!(a > b)\n",
+            "  lower implies: a > b becomes !a | b\n",
+            "  build: !b becomes [!b]\n",
+            "simplify\n",
+            "  unit propagation: a removes [a, c]\n",
+        ] {
+            assert!(shown.contains(line), "{} is missing from\n{}", line, shown);
+        }
+    }
+}
@@ -1,94 +1,159 @@
-use std::collections::HashMap;
+use super::{parser::{ExpressionParseError, ExpressionParser}, rewrite::RewriteStep, VariableNames};
 
-use super::{parser::{ExpressionParseError, ExpressionParser}, VariableNames};
-
-#[derive(Debug, Clone)]
-pub(super) struct Expression{
+/// A formula over variables
+/// Parse one with Expression::parse or build one from Expression::variable and the methods below,
+/// or with operators on Vars
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expression{
     node: Box<ExpressionNode>
 }
 impl Expression{
-    pub(super) fn parse_string(input: &str)->Result<(Self, VariableNames), ExpressionParseError>{
-        let mut map = HashMap::new();
-        let node = ExpressionParser::parse_string(&input, &mut map)?;
-        let var_names = VariableNames{
-            names: map,
-        };
-        Ok((
-            Self{
-                node
-            },
-            var_names
-        ))
-    }
-    pub(super) fn parse_string_with_variable_names(input: &str, variable_names: &mut VariableNames)->Result<Self, ExpressionParseError>{
-        let node = ExpressionParser::parse_string(&input, &mut variable_names.names)?;
+    /// New variable names are added to names, unless it fails to parse
+    /// 0 and 1 on their own are the constants false and true, not variables
+    pub fn parse(input: &str, names: &mut VariableNames)->Result<Self, ExpressionParseError>{
+        let known = names.names.len();
+        let node = ExpressionParser::parse_string(input, &mut names.names).inspect_err(|_|{
+            //a quantifier can make thousands of names before it fails
+            names.names.retain(|_, var|var.index() < known);
+        })?;
         Ok(Self{
             node
         })
     }
+    pub fn variable(var: Variable)->Self{Self::from_node(ExpressionNode::new_variable(var))}
+    pub fn constant(value: bool)->Self{Self::from_node(ExpressionNode::new_constant(value))}
+    pub fn and(self, other: impl Into<Self>)->Self{Self::from_node(ExpressionNode::new_and(self.node, other.into().node))}
+    pub fn or(self, other: impl Into<Self>)->Self{Self::from_node(ExpressionNode::new_or(self.node, other.into().node))}
+    pub fn xor(self, other: impl Into<Self>)->Self{Self::from_node(ExpressionNode::new_xor(self.node, other.into().node))}
+    pub fn implies(self, other: impl Into<Self>)->Self{Self::from_node(ExpressionNode::new_implies(self.node, other.into().node))}
+    pub fn iff(self, other: impl Into<Self>)->Self{Self::from_node(ExpressionNode::new_biconditional(self.node, other.into().node))}
+    pub fn nand(self, other: impl Into<Self>)->Self{Self::from_node(ExpressionNode::new_nand(self.node, other.into().node))}
+    pub fn nor(self, other: impl Into<Self>)->Self{Self::from_node(ExpressionNode::new_nor(self.node, other.into().node))}
+
+    pub(super) fn from_node(node: Box<ExpressionNode>)->Self{
+        Self{node}
+    }
+    pub(super) fn node(&self)->&ExpressionNode{
+        &self.node
+    }
     pub(super) fn node_owned(self)->Box<ExpressionNode>{
         self.node
     }
 }
+impl std::ops::Not for Expression{
+    type Output = Self;
+    fn not(self)->Self{
+        Self::from_node(ExpressionNode::new_not(self.node))
+    }
+}
 
 use super::variable::Variable;
 
-#[derive(Debug, Clone)]
-pub(super) enum ExpressionNode{
+/// Xor, Implies, Iff, Nand and Nor are kept as written
+/// and only lowered to And, Or and Not when building a KnowledgeBase
+/// Constant(true) is 1 and Constant(false) is 0
+/// Public so rewrite rules can match on it
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExpressionNode{
     And(Box<Self>, Box<Self>),
     Or(Box<Self>, Box<Self>),
     Not(Box<Self>),
-    Variable(Variable)
+    Xor(Box<Self>, Box<Self>),
+    Implies(Box<Self>, Box<Self>),
+    Iff(Box<Self>, Box<Self>),
+    Nand(Box<Self>, Box<Self>),
+    Nor(Box<Self>, Box<Self>),
+    Variable(Variable),
+    Constant(bool)
 }
 
 impl ExpressionNode{
     pub(super) fn new_variable(var: Variable)->Box<Self>{Box::new(Self::Variable(var))}
+    pub(super) fn new_constant(value: bool)->Box<Self>{Box::new(Self::Constant(value))}
     pub(super) fn new_and(a: Box<Self>, b: Box<Self>)->Box<Self>{Box::new(Self::And(a, b))}
     pub(super) fn new_or(a: Box<Self>, b: Box<Self>)->Box<Self>{Box::new(Self::Or(a, b))}
     pub(super) fn new_not(a: Box<Self>)->Box<Self>{Box::new(Self::Not(a))}
-
-    pub(super) fn new_xor(a: Box<Self>, b: Box<Self>) -> Box<Self> {
-        let or = Self::new_or(a.clone(), b.clone());
-        let and = Self::new_and(a, b);
-        Self::new_and(or, Self::new_not(and))
-    }
-    pub(super) fn new_implies(a: Box<Self>, b: Box<Self>) -> Box<Self> {
-        Self::new_or(Self::new_not(a), b)
-    }
-    pub(super) fn new_biconditional(a: Box<Self>, b: Box<Self>) -> Box<Self> {
-        let or = Self::new_or(a.clone(), b.clone());
-        let and = Self::new_and(a, b);
-        Self::new_or(Self::new_not(or), and)
-    }
+    pub(super) fn new_xor(a: Box<Self>, b: Box<Self>)->Box<Self>{Box::new(Self::Xor(a, b))}
+    pub(super) fn new_implies(a: Box<Self>, b: Box<Self>)->Box<Self>{Box::new(Self::Implies(a, b))}
+    pub(super) fn new_biconditional(a: Box<Self>, b: Box<Self>)->Box<Self>{Box::new(Self::Iff(a, b))}
+    pub(super) fn new_nand(a: Box<Self>, b: Box<Self>)->Box<Self>{Box::new(Self::Nand(a, b))}
+    pub(super) fn new_nor(a: Box<Self>, b: Box<Self>)->Box<Self>{Box::new(Self::Nor(a, b))}
 }
 
 impl ExpressionNode{
-    fn traverse_owned(mut self: Box<Self>, closure: fn(Box<Self>)->Box<Self>)->Box<Self>{
-        self = closure(self);
-        // self = self.pushdown_not();
+    /// Rebuilds this node with closure run on each child
+    fn map_children(self: Box<Self>, mut closure: impl FnMut(Box<Self>)->Box<Self>)->Box<Self>{
+        match *self {
+            Self::And(a, b) => Self::new_and(closure(a), closure(b)),
+            Self::Or(a, b) => Self::new_or(closure(a), closure(b)),
+            Self::Xor(a, b) => Self::new_xor(closure(a), closure(b)),
+            Self::Implies(a, b) => Self::new_implies(closure(a), closure(b)),
+            Self::Iff(a, b) => Self::new_biconditional(closure(a), closure(b)),
+            Self::Nand(a, b) => Self::new_nand(closure(a), closure(b)),
+            Self::Nor(a, b) => Self::new_nor(closure(a), closure(b)),
+            Self::Not(a) => Self::new_not(closure(a)),
+            Self::Variable(_) | Self::Constant(_) => self,
+        }
+    }
 
+    /// Runs closure on this node then on the children of whatever it returns
+    pub(super) fn traverse_owned(self: Box<Self>, closure: &mut impl FnMut(Box<Self>)->Box<Self>)->Box<Self>{
         //run again on children
+        closure(self).map_children(|child|child.traverse_owned(closure))
+    }
+
+    /// Runs rewrite on this node, and if rule is Some pushes the step onto trace if there is one
+    fn traced(self: Box<Self>, rule: Option<&str>, trace: Option<&mut Vec<RewriteStep>>, rewrite: impl FnOnce(Box<Self>)->Box<Self>)->Box<Self>{
+        let (Some(rule), Some(trace)) = (rule, trace) else {return rewrite(self)};
+        let before = self.clone();
+        let after = rewrite(self);
+        trace.push(RewriteStep{rule: rule.to_string(), before, after: after.clone()});
+        after
+    }
+
+    /// Replaces this node with one only using And, Or and Not
+    /// a ^ b becomes (a | b) & !(a & b)
+    /// a > b becomes !a | b
+    /// a <> b becomes !(a | b) | (a & b)
+    /// a !& b becomes !(a & b)
+    /// a !| b becomes !(a | b)
+    fn lower(self: Box<Self>)->Box<Self>{
         match *self {
-            Self::And(mut a, mut b) => {
-                a = a.traverse_owned(closure);
-                b = b.traverse_owned(closure);
-                Self::new_and(a, b)
-            },
-            Self::Or(mut a, mut b) => {
-                a = a.traverse_owned(closure);
-                b = b.traverse_owned(closure);
-                Self::new_or(a, b)
+            Self::Xor(a, b) => {
+                let or = Self::new_or(a.clone(), b.clone());
+                let and = Self::new_and(a, b);
+                Self::new_and(or, Self::new_not(and))
             },
-            Self::Variable(variable) => {Self::new_variable(variable)},
-            Self::Not(mut a) => {
-                a = a.traverse_owned(closure);
-                Self::new_not(a)
+            Self::Implies(a, b) => Self::new_or(Self::new_not(a), b),
+            Self::Iff(a, b) => {
+                let or = Self::new_or(a.clone(), b.clone());
+                let and = Self::new_and(a, b);
+                Self::new_or(Self::new_not(or), and)
             },
+            Self::Nand(a, b) => Self::new_not(Self::new_and(a, b)),
+            Self::Nor(a, b) => Self::new_not(Self::new_or(a, b)),
+            _ => self,
         }
     }
+    fn lower_rule(&self)->Option<&'static str>{
+        match self {
+            Self::Xor(..) => Some("lower xor"),
+            Self::Implies(..) => Some("lower implies"),
+            Self::Iff(..) => Some("lower iff"),
+            Self::Nand(..) => Some("lower nand"),
+            Self::Nor(..) => Some("lower nor"),
+            _ => None,
+        }
+    }
+    pub(super) fn lower_recursive(self: Box<Self>, mut trace: Option<&mut Vec<RewriteStep>>)->Box<Self>{
+        self.traverse_owned(&mut |node: Box<Self>|{
+            let rule = node.lower_rule();
+            node.traced(rule, trace.as_deref_mut(), Self::lower)
+        })
+    }
 
     // Garuntees this node is not longer NOT by pushing down the nots
-    fn pushdown_not(self: Box<Self>)->Box<Self>{
+    pub(super) fn pushdown_not(self: Box<Self>)->Box<Self>{
         //nothing changes in this case
         let Self::Not(node) = *self else {return self};
 
@@ -99,20 +164,38 @@ impl ExpressionNode{
             Self::Or(a, b) => {
                 Self::And(Box::new(Self::Not(a)), Box::new(Self::Not(b)))
             },
-            Self::Not(a) => {*a},
+            Self::Not(a) => {return a.pushdown_not()},
 
             //nothing changes in this case
             Self::Variable(variable) => {Self::Not(Box::new(Self::Variable(variable)))},
+
+            Self::Constant(value) => {Self::Constant(!value)},
+
+            node => {return Self::new_not(Box::new(node).lower()).pushdown_not()},
         })
     }
 
-    pub fn pushdown_not_recursive(self: Box<Self>)->Box<Self>{
-        self.traverse_owned(|node: Box<Self>|node.pushdown_not())
+    fn pushdown_not_rule(&self)->Option<&'static str>{
+        let Self::Not(node) = self else {return None};
+        match **node {
+            Self::And(..) | Self::Or(..) => Some("de morgan"),
+            Self::Not(_) => Some("double negation"),
+            Self::Variable(_) => None,
+            Self::Constant(_) => Some("negate constant"),
+            _ => Some("lower under not"),
+        }
+    }
+    pub(super) fn pushdown_not_recursive(self: Box<Self>, mut trace: Option<&mut Vec<RewriteStep>>)->Box<Self>{
+        self.traverse_owned(&mut |node: Box<Self>|{
+            let rule = node.pushdown_not_rule();
+            node.traced(rule, trace.as_deref_mut(), Self::pushdown_not)
+        })
     }
 
     /// Replaces A | (B & C) with (A | B) & (A | C)
     /// Replaces (B & C) | A with (B | A) & (C | A)
     /// Garuntees this node is no longer OR with AND inside
+    /// The children must already be distributed
     fn distribute_or(self: Box<Self>)->Box<Self>{
         //nothing changes in this case
         let Self::Or(a, b) = *self else {return self};
@@ -120,20 +203,89 @@ impl ExpressionNode{
         //if (a != and & b != and) {retrun self}
         if let Self::And(a_a, a_b) = *b {
             Self::new_and(
-                Self::new_or(a.clone(), a_a), 
-                Self::new_or(a, a_b)
+                Self::new_or(a.clone(), a_a).distribute_or(), 
+                Self::new_or(a, a_b).distribute_or()
             )
         }else if let Self::And(b_a, b_b) = *a {
             Self::new_and(
-                Self::new_or(b_a, b.clone()),
-                Self::new_or(b_b, b), 
+                Self::new_or(b_a, b.clone()).distribute_or(),
+                Self::new_or(b_b, b).distribute_or(), 
             )
         }else{
             Self::new_or(a, b)
         }
     }
-    pub fn distribute_or_recursive(self: Box<Self>)->Box<Self>{
-        self.traverse_owned(|node: Box<Self>|node.distribute_or())
+    /// Children first so an OR never ends up with an AND below it
+    pub(super) fn distribute_or_recursive(self: Box<Self>, mut trace: Option<&mut Vec<RewriteStep>>)->Box<Self>{
+        match *self {
+            Self::And(a, b) => Self::new_and(a.distribute_or_recursive(trace.as_deref_mut()), b.distribute_or_recursive(trace)),
+            Self::Or(a, b) => {
+                let or = Self::new_or(a.distribute_or_recursive(trace.as_deref_mut()), b.distribute_or_recursive(trace.as_deref_mut()));
+                let rule = match &*or {
+                    Self::Or(a, b) if matches!(**a, Self::And(..)) || matches!(**b, Self::And(..)) => Some("distribute"),
+                    _ => None,
+                };
+                or.traced(rule, trace, Self::distribute_or)
+            },
+            _ => self,
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::test_helpers::{node_holds, random_node, Random};
+
+    fn only_and_or_not(node: &ExpressionNode)->bool{
+        match node {
+            ExpressionNode::Variable(_) | ExpressionNode::Constant(_) => true,
+            ExpressionNode::Not(a) => only_and_or_not(a),
+            ExpressionNode::And(a, b) | ExpressionNode::Or(a, b) => only_and_or_not(a) && only_and_or_not(b),
+            _ => false,
+        }
     }
 
-}
\ No newline at end of file
+    /// Every operator has the same truth table after lowering, constants included
+    #[test]
+    fn lower_keeps_truth_tables(){
+        let a = ||ExpressionNode::new_variable(Variable::new(0));
+        let b = ||ExpressionNode::new_variable(Variable::new(1));
+        let operands = [
+            (a(), b()),
+            (a(), ExpressionNode::new_constant(true)),
+            (ExpressionNode::new_constant(false), b()),
+        ];
+        for (a, b) in operands {
+            for node in [
+                ExpressionNode::new_xor(a.clone(), b.clone()),
+                ExpressionNode::new_implies(a.clone(), b.clone()),
+                ExpressionNode::new_biconditional(a.clone(), b.clone()),
+                ExpressionNode::new_nand(a.clone(), b.clone()),
+                ExpressionNode::new_nor(a.clone(), b.clone()),
+                ExpressionNode::new_constant(true),
+                ExpressionNode::new_constant(false),
+            ] {
+                let lowered = node.clone().lower();
+                assert!(only_and_or_not(&lowered), "{:?}", lowered);
+                for assignment in 0..4 {
+                    assert_eq!(node_holds(&lowered, assignment), node_holds(&node, assignment), "{:?}", node);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn lower_recursive_keeps_truth_tables(){
+        let mut random = Random(0x6C8E9CF570932BD5);
+        for _ in 0..1000 {
+            let node = random_node(&mut random, 5);
+            let lowered = node.clone().lower_recursive(None);
+            assert!(only_and_or_not(&lowered));
+            for assignment in 0..1 << 5 {
+                assert_eq!(node_holds(&lowered, assignment), node_holds(&node, assignment));
+            }
+        }
+    }
+}
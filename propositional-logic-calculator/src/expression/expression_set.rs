@@ -0,0 +1,272 @@
+use super::{expression::Expression, knowledge_base::KnowledgeBaseFact, parser::ExpressionParseError, printer::ExpressionPrinter, puzzle::Count, variable::Variable, KnowledgeBase, VariableNames};
+
+/// What an entry says, a puzzle count is too big as an expression so it goes straight to facts
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Content{
+    Expression(Expression),
+    Count(Count)
+}
+
+/// An expression, the label it is known by and its facts
+/// The facts are converted once when the expression is added,
+/// with fresh variables from names for the parts that would blow up if distributed
+/// A soft expression has a weight, it is what breaking it costs instead of having to hold
+#[derive(Debug, Clone)]
+pub(super) struct ExpressionEntry{
+    label: String,
+    content: Content,
+    weight: Option<u64>,
+    facts: Vec<KnowledgeBaseFact>
+}
+impl ExpressionEntry{
+    fn new(label: String, content: Content, weight: Option<u64>, names: &mut VariableNames)->Self{
+        let facts = match &content {
+            Content::Expression(expression) => {
+                let (kb, next_fresh) = KnowledgeBase::from_expression_defined(expression.clone(), names.len());
+                names.reserve(next_fresh);
+                kb.facts().clone()
+            },
+            Content::Count(count) => {
+                let mut next_fresh = names.len();
+                let facts = count.facts(&mut ||{
+                    next_fresh += 1;
+                    Variable::new(next_fresh - 1)
+                });
+                names.reserve(next_fresh);
+                facts
+            },
+        };
+        Self{label, content, weight, facts}
+    }
+    pub(super) fn label(&self)->&str{
+        &self.label
+    }
+    pub(super) fn content(&self)->&Content{
+        &self.content
+    }
+    /// None for a count
+    pub(super) fn expression(&self)->Option<&Expression>{
+        match &self.content {
+            Content::Expression(expression) => Some(expression),
+            Content::Count(_) => None,
+        }
+    }
+    /// The expression printed, or the count as it was typed
+    pub(super) fn display(&self, printer: &ExpressionPrinter)->String{
+        match &self.content {
+            Content::Expression(expression) => printer.print(expression.node()),
+            Content::Count(count) => count.to_string(),
+        }
+    }
+    /// None if it is hard
+    pub(super) fn weight(&self)->Option<u64>{
+        self.weight
+    }
+    pub(super) fn facts(&self)->&Vec<KnowledgeBaseFact>{
+        &self.facts
+    }
+}
+
+/// Something that was done to the set, enough to undo or redo it
+#[derive(Debug, Clone)]
+enum Edit{
+    /// Pushed onto the end
+    Add(ExpressionEntry),
+    Retract(usize, ExpressionEntry),
+    Replace{index: usize, old: ExpressionEntry, new: ExpressionEntry}
+}
+
+/**
+    Every expression in the order it was added, each with a label that stays the same
+    Expressions without a label are numbered, the first is 1
+
+    Every change is remembered so it can be undone,
+    making a new change forgets anything that could have been redone.
+*/
+#[derive(Debug, Default)]
+pub(super) struct ExpressionSet{
+    entries: Vec<ExpressionEntry>,
+    next_number: usize,
+    undo: Vec<Edit>,
+    redo: Vec<Edit>
+}
+impl ExpressionSet{
+    pub(super) fn new()->Self{
+        Self{next_number: 1, ..Self::default()}
+    }
+    /// Labelled expressions and their weights as they were saved, with nothing to undo
+    pub(super) fn restore(labelled: Vec<(String, Content, Option<u64>)>, next_number: usize, names: &mut VariableNames)->Result<Self, ExpressionParseError>{
+        let mut set = Self::new();
+        for (label, content, weight) in labelled {
+            set.push(Some(&label), content, weight, names)?;
+        }
+        set.next_number = next_number;
+        set.undo.clear();
+        Ok(set)
+    }
+    /// The number the next expression without a label gets
+    pub(super) fn next_number(&self)->usize{
+        self.next_number
+    }
+    pub(super) fn entries(&self)->&Vec<ExpressionEntry>{
+        &self.entries
+    }
+    fn hard(&self)->impl Iterator<Item = &ExpressionEntry>{
+        self.entries.iter().filter(|entry|entry.weight.is_none())
+    }
+    /// Only the hard ones, soft expressions aren't part of the knowledge base
+    /// Counts are left out too, they have no expression
+    pub(super) fn expressions(&self)->Vec<&Expression>{
+        self.hard().filter_map(|entry|entry.expression()).collect()
+    }
+    /// All the cached facts of hard expressions together, nothing is converted again
+    pub(super) fn knowledge_base(&self)->KnowledgeBase{
+        KnowledgeBase::from_facts(self.hard().flat_map(|entry|entry.facts().iter().cloned()).collect())
+    }
+    pub(super) fn soft(&self)->Vec<&ExpressionEntry>{
+        self.entries.iter().filter(|entry|entry.weight.is_some()).collect()
+    }
+    pub(super) fn entry(&self, label: &str)->Result<&ExpressionEntry, ExpressionParseError>{
+        self.index_of(label).map(|index|&self.entries[index])
+    }
+    fn index_of(&self, label: &str)->Result<usize, ExpressionParseError>{
+        self.entries.iter().position(|entry|entry.label == label).ok_or(ExpressionParseError::UnknownLabel)
+    }
+
+    pub(super) fn push(&mut self, label: Option<&str>, content: Content, weight: Option<u64>, names: &mut VariableNames)->Result<&ExpressionEntry, ExpressionParseError>{
+        let label = match label {
+            Some(label) if self.index_of(label).is_ok() => return Err(ExpressionParseError::DuplicateLabel),
+            Some(label) => label.to_string(),
+            //skip numbers someone already used as a label
+            None => loop {
+                let label = self.next_number.to_string();
+                self.next_number += 1;
+                if self.index_of(&label).is_err() {
+                    break label;
+                }
+            },
+        };
+        self.apply(Edit::Add(ExpressionEntry::new(label, content, weight, names)));
+        Ok(self.entries.last().expect("just pushed"))
+    }
+    pub(super) fn retract(&mut self, label: &str)->Result<(), ExpressionParseError>{
+        let index = self.index_of(label)?;
+        self.apply(Edit::Retract(index, self.entries[index].clone()));
+        Ok(())
+    }
+    /// Keeps the label and the position, the weight is the new one
+    pub(super) fn replace(&mut self, label: &str, content: Content, weight: Option<u64>, names: &mut VariableNames)->Result<(), ExpressionParseError>{
+        let index = self.index_of(label)?;
+        let old = self.entries[index].clone();
+        let new = ExpressionEntry::new(old.label.clone(), content, weight, names);
+        self.apply(Edit::Replace{index, old, new});
+        Ok(())
+    }
+    fn apply(&mut self, edit: Edit){
+        self.redo.clear();
+        self.run(&edit);
+        self.undo.push(edit);
+    }
+
+    /// false if there was nothing to undo
+    /// Edits are undone newest first so an added entry is still the last one
+    pub(super) fn undo(&mut self)->bool{
+        let Some(edit) = self.undo.pop() else {return false};
+        match &edit {
+            Edit::Add(_) => {self.entries.pop();},
+            Edit::Retract(index, entry) => self.entries.insert(*index, entry.clone()),
+            Edit::Replace{index, old, ..} => self.entries[*index] = old.clone(),
+        }
+        self.redo.push(edit);
+        true
+    }
+    /// false if there was nothing to redo
+    pub(super) fn redo(&mut self)->bool{
+        let Some(edit) = self.redo.pop() else {return false};
+        self.run(&edit);
+        self.undo.push(edit);
+        true
+    }
+
+    fn run(&mut self, edit: &Edit){
+        match edit {
+            Edit::Add(entry) => self.entries.push(entry.clone()),
+            Edit::Retract(index, _) => {self.entries.remove(*index);},
+            Edit::Replace{index, new, ..} => self.entries[*index] = new.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn variable(var: u32)->Content{
+        Content::Expression(Expression::variable(Variable::new(var)))
+    }
+    fn labels(set: &ExpressionSet)->Vec<&str>{
+        set.entries().iter().map(|entry|entry.label()).collect()
+    }
+
+    #[test]
+    fn undo_after_retract(){
+        let mut set = ExpressionSet::new();
+        let mut names = VariableNames::new();
+        for (label, var) in [("a", 0), ("b", 1), ("c", 2)] {
+            set.push(Some(label), variable(var), None, &mut names).unwrap();
+        }
+        set.retract("b").unwrap();
+        assert_eq!(labels(&set), ["a", "c"]);
+        assert_eq!(set.knowledge_base().facts().len(), 2);
+
+        //back in the middle where it was, facts and all
+        assert!(set.undo());
+        assert_eq!(labels(&set), ["a", "b", "c"]);
+        assert_eq!(set.entry("b").unwrap().content(), &variable(1));
+        assert_eq!(set.knowledge_base().facts().len(), 3);
+
+        assert!(set.redo());
+        assert_eq!(labels(&set), ["a", "c"]);
+        assert!(!set.redo());
+    }
+
+    #[test]
+    fn a_new_edit_clears_redo(){
+        let mut set = ExpressionSet::new();
+        let mut names = VariableNames::new();
+        set.push(Some("a"), variable(0), None, &mut names).unwrap();
+        set.push(Some("b"), variable(1), None, &mut names).unwrap();
+        assert!(set.undo());
+        set.replace("a", variable(2), Some(3), &mut names).unwrap();
+        assert!(!set.redo());
+        assert_eq!(labels(&set), ["a"]);
+        assert_eq!(set.entry("a").unwrap().weight(), Some(3));
+
+        assert!(set.undo());
+        assert!(set.undo());
+        assert!(!set.undo());
+        assert!(set.entries().is_empty());
+    }
+
+    #[test]
+    fn duplicate_labels(){
+        let mut set = ExpressionSet::new();
+        let mut names = VariableNames::new();
+        set.push(Some("x"), variable(0), None, &mut names).unwrap();
+        assert!(matches!(set.push(Some("x"), variable(1), None, &mut names), Err(ExpressionParseError::DuplicateLabel)));
+        assert_eq!(labels(&set), ["x"]);
+        //the failed push left nothing to undo
+        assert!(set.undo());
+        assert!(!set.undo());
+
+        //once it is gone the label is free again
+        set.push(Some("x"), variable(1), None, &mut names).unwrap();
+        assert!(matches!(set.retract("y"), Err(ExpressionParseError::UnknownLabel)));
+
+        //numbers skip one already used as a label
+        set.push(Some("2"), variable(2), None, &mut names).unwrap();
+        set.push(None, variable(3), None, &mut names).unwrap();
+        set.push(None, variable(4), None, &mut names).unwrap();
+        assert_eq!(labels(&set), ["x", "2", "1", "3"]);
+    }
+}
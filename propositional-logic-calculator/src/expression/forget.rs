@@ -0,0 +1,101 @@
+use std::collections::BTreeSet;
+
+use super::{knowledge_base::{KnowledgeBaseFact, KnowledgeBaseLiteral}, prime::add_minimal, parser::ExpressionParseError, variable::Variable, KnowledgeBase, Workspace};
+
+impl KnowledgeBase{
+    /**
+        What the KB says about everything except vars
+
+        Each variable is eliminated by resolution, every fact with it is replaced
+        by every resolvent of a fact with it and a fact with it negated.
+        The result has a model exactly when the KB has one that agrees on every other variable.
+        The variable with the fewest resolvents goes first and subsumed facts are dropped,
+        but forgetting can still make exponentially many facts.
+    */
+    pub fn forget(&self, vars: &[Variable])->KnowledgeBase{
+        let mut facts: Vec<KnowledgeBaseFact> = Vec::new();
+        for fact in self.facts().iter().filter(|fact|!fact.tautology()) {
+            add_minimal(&mut facts, fact.clone());
+        }
+
+        let mut remaining: BTreeSet<Variable> = vars.iter().cloned().collect();
+        let occurrences = |facts: &[KnowledgeBaseFact], literal: &KnowledgeBaseLiteral|facts.iter().filter(|fact|fact.contains(literal)).count();
+        while let Some(var) = remaining.iter().min_by_key(|var|{
+            let positive = KnowledgeBaseLiteral::new(false, **var);
+            occurrences(&facts, &positive) * occurrences(&facts, &positive.negated())
+        }).cloned() {
+            remaining.remove(&var);
+            let positive = KnowledgeBaseLiteral::new(false, var);
+            let negative = positive.negated();
+            let (mentioned, kept): (Vec<KnowledgeBaseFact>, Vec<KnowledgeBaseFact>) = facts
+                .into_iter()
+                .partition(|fact|fact.contains(&positive) || fact.contains(&negative));
+            facts = kept;
+            let (with, without): (Vec<&KnowledgeBaseFact>, Vec<&KnowledgeBaseFact>) = mentioned.iter().partition(|fact|fact.contains(&positive));
+            for a in with.iter() {
+                for b in without.iter() {
+                    let resolvent = KnowledgeBaseFact::new(
+                        a.literals().iter().chain(b.literals()).filter(|literal|*literal.var() != var).cloned().collect()
+                    );
+                    if !resolvent.tautology() {
+                        add_minimal(&mut facts, resolvent);
+                    }
+                }
+            }
+        }
+        KnowledgeBase::from_facts(facts)
+    }
+    /// What the KB says about only vars, everything else is forgotten
+    pub fn project(&self, vars: &[Variable])->KnowledgeBase{
+        let others: BTreeSet<Variable> = self.facts()
+            .iter()
+            .flat_map(|fact|fact.literals())
+            .map(|literal|*literal.var())
+            .filter(|var|!vars.contains(var))
+            .collect();
+        self.forget(&others.into_iter().collect::<Vec<_>>())
+    }
+}
+
+impl Workspace{
+    /// :forget anna, josh prints the knowledge base without anna and josh, :project anna, josh with only them
+    pub(super) fn parse_forget(&mut self, input: &str, project: bool)->Result<String, ExpressionParseError>{
+        let vars = input
+            .split(',')
+            .map(|name|self.variable_names.find(name.trim()))
+            .collect::<Result<Vec<Variable>, _>>()?;
+        //fresh variables from converting are always forgotten
+        let kb = self.expression_set.knowledge_base();
+        let kb = if project {kb.project(&vars)} else {kb.forget(&[vars, self.fresh_variables(&kb)].concat())};
+        if kb.facts().is_empty() {
+            return Ok("Nothing, every assignment of the rest is part of a model\n\n".to_string());
+        }
+        Ok(format!("{}\n", kb.display(&self.variable_names)))
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::test_helpers::{holds, random_facts, Random};
+
+    /// An assignment of the rest is a model of the result exactly when some value of the forgotten ones extends it
+    #[test]
+    fn keeps_every_model_of_the_rest(){
+        let mut random = Random(0xDA942042E4DD58B5);
+        let variables = 6u32;
+        for _ in 0..200 {
+            let count = random.below(10);
+            let facts = random_facts(&mut random, count, 0..variables);
+            let forgotten: u32 = (0..variables).filter(|_|random.below(2) == 0).fold(0, |mask, var|mask | 1 << var);
+            let vars: Vec<Variable> = (0..variables).filter(|var|forgotten >> var & 1 == 1).map(Variable::new).collect();
+
+            let result = KnowledgeBase::from_facts(facts.clone()).forget(&vars);
+            assert!(result.facts().iter().flat_map(|fact|fact.literals()).all(|literal|!vars.contains(literal.var())));
+            for rest in (0..1u32 << variables).filter(|assignment|assignment & forgotten == 0) {
+                let extends = (0..1u32 << variables).filter(|values|values & !forgotten == 0).any(|values|holds(&facts, rest | values));
+                assert_eq!(holds(result.facts(), rest), extends);
+            }
+        }
+    }
+}
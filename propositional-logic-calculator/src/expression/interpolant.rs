@@ -0,0 +1,239 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{knowledge_base::{KnowledgeBaseFact, KnowledgeBaseLiteral}, parser::ExpressionParseError, printer::ExpressionPrinter, rewrite::Rewriter, variable::Variable, Expression, KnowledgeBase, Workspace};
+
+/// One step of a resolution refutation
+enum ProofStep{
+    /// A fact that was given, from A when from_a
+    Given{from_a: bool},
+    /// Resolves two earlier steps, positive has pivot and negative has !pivot
+    Resolved{pivot: Variable, positive: usize, negative: usize}
+}
+
+/**
+    Finds a resolution refutation of facts, keeping every step
+
+    The Solver is faster but doesn't remember how it got its learned facts,
+    so this is a plain search with unit propagation that builds the proof as it backs out.
+    Every search returns a step whose fact is false under the values so far.
+    A value forced by a unit fact is resolved against that fact,
+    and a branch that needed both values resolves the two facts they ended in.
+    There is no learning so it can take exponentially long on a hard contradiction.
+*/
+struct Refuter<'a>{
+    given: &'a [(KnowledgeBaseFact, bool)],
+    values: HashMap<Variable, bool>,
+    steps: Vec<(ProofStep, KnowledgeBaseFact)>,
+    //the step of every given fact once it has been used
+    given_steps: Vec<Option<usize>>
+}
+impl<'a> Refuter<'a>{
+    fn new(given: &'a [(KnowledgeBaseFact, bool)])->Self{
+        Self{given, values: HashMap::new(), steps: Vec::new(), given_steps: vec![None; given.len()]}
+    }
+    fn holds(&self, literal: &KnowledgeBaseLiteral)->Option<bool>{
+        self.values.get(literal.var()).map(|value|*value != literal.not())
+    }
+    fn fact(&self, step: usize)->&KnowledgeBaseFact{
+        &self.steps[step].1
+    }
+    fn given_step(&mut self, index: usize)->usize{
+        if let Some(step) = self.given_steps[index] {
+            return step;
+        }
+        let (fact, from_a) = &self.given[index];
+        self.steps.push((ProofStep::Given{from_a: *from_a}, fact.clone()));
+        self.given_steps[index] = Some(self.steps.len() - 1);
+        self.steps.len() - 1
+    }
+    /// with has literal and without has it negated
+    fn resolve(&mut self, literal: &KnowledgeBaseLiteral, with: usize, without: usize)->usize{
+        let var = *literal.var();
+        let resolvent = KnowledgeBaseFact::new(
+            self.fact(with).literals().iter().chain(self.fact(without).literals()).filter(|other|*other.var() != var).cloned().collect()
+        );
+        let (positive, negative) = if literal.not() {(without, with)} else {(with, without)};
+        self.steps.push((ProofStep::Resolved{pivot: var, positive, negative}, resolvent));
+        self.steps.len() - 1
+    }
+    /// Searches with literal true, then takes it back
+    fn branch(&mut self, literal: &KnowledgeBaseLiteral)->Option<usize>{
+        self.values.insert(*literal.var(), !literal.not());
+        let step = self.refute();
+        self.values.remove(literal.var());
+        step
+    }
+    /// The step of a fact that is false under the values, None if they extend to a model
+    fn refute(&mut self)->Option<usize>{
+        let mut unit = None;
+        let mut open = None;
+        for (index, (fact, _)) in self.given.iter().enumerate() {
+            if fact.literals().iter().any(|literal|self.holds(literal) == Some(true)) {
+                continue;
+            }
+            let mut unassigned = fact.literals().iter().filter(|literal|self.holds(literal).is_none());
+            match (unassigned.next(), unassigned.next()) {
+                (None, _) => return Some(self.given_step(index)),
+                (Some(literal), None) => {unit.get_or_insert((index, literal.clone()));},
+                (Some(literal), Some(_)) => {open.get_or_insert(literal.clone());},
+            }
+        }
+
+        if let Some((index, literal)) = unit {
+            let step = self.branch(&literal)?;
+            if !self.fact(step).contains(&literal.negated()) {
+                return Some(step);
+            }
+            let reason = self.given_step(index);
+            return Some(self.resolve(&literal, reason, step));
+        }
+
+        //None once every fact holds
+        let literal = open?;
+        let first = self.branch(&literal)?;
+        if !self.fact(first).contains(&literal.negated()) {
+            return Some(first);
+        }
+        let second = self.branch(&literal.negated())?;
+        if !self.fact(second).contains(&literal) {
+            return Some(second);
+        }
+        Some(self.resolve(&literal, second, first))
+    }
+}
+
+/// A partial interpolant with constants folded away as it is built
+#[derive(Clone)]
+enum Partial{
+    Constant(bool),
+    Expression(Expression)
+}
+impl Partial{
+    fn join(a: &Partial, b: &Partial, and: bool)->Partial{
+        match (a, b) {
+            (Partial::Constant(value), other) | (other, Partial::Constant(value)) => {
+                if *value == and {other.clone()} else {Partial::Constant(*value)}
+            },
+            (Partial::Expression(a), Partial::Expression(b)) => {
+                Partial::Expression(if and {a.clone().and(b.clone())} else {a.clone().or(b.clone())})
+            },
+        }
+    }
+    fn into_expression(self)->Expression{
+        match self {
+            Partial::Constant(value) => Expression::constant(value),
+            Partial::Expression(expression) => expression,
+        }
+    }
+}
+
+impl KnowledgeBase{
+    /**
+        A Craig interpolant between this and other, None if they have a model together
+
+        The interpolant follows from this, contradicts other,
+        and only has variables both of them have.
+        It is read off a resolution refutation of both with McMillan's rules:
+        a fact of this gives the or of its literals other also has, a fact of other gives 1,
+        a resolution on a variable only this has joins the two sides with or, and anything else with and.
+    */
+    pub fn interpolant(&self, other: &KnowledgeBase)->Option<Expression>{
+        let given: Vec<(KnowledgeBaseFact, bool)> = self.facts()
+            .iter()
+            .map(|fact|(fact.clone(), true))
+            .chain(other.facts().iter().map(|fact|(fact.clone(), false)))
+            .filter(|(fact, _)|!fact.tautology())
+            .collect();
+        let mut refuter = Refuter::new(&given);
+        let root = refuter.refute()?;
+
+        let in_other: HashSet<Variable> = other.facts().iter().flat_map(|fact|fact.literals()).map(|literal|*literal.var()).collect();
+        let mut partials: Vec<Partial> = Vec::with_capacity(refuter.steps.len());
+        for (step, fact) in refuter.steps.iter() {
+            let partial = match step {
+                ProofStep::Given{from_a: true} => fact.literals()
+                    .iter()
+                    .filter(|literal|in_other.contains(literal.var()))
+                    .map(|literal|{
+                        let var = Expression::variable(*literal.var());
+                        Partial::Expression(if literal.not() {!var} else {var})
+                    })
+                    .fold(Partial::Constant(false), |all, literal|Partial::join(&all, &literal, false)),
+                ProofStep::Given{from_a: false} => Partial::Constant(true),
+                ProofStep::Resolved{pivot, positive, negative} => {
+                    Partial::join(&partials[*positive], &partials[*negative], in_other.contains(pivot))
+                },
+            };
+            partials.push(partial);
+        }
+        Some(partials.swap_remove(root).into_expression())
+    }
+}
+
+impl Workspace{
+    /// An interpolant between the expressions labelled a and the ones labelled b, None if they have a model together
+    pub fn interpolant(&self, a: &[&str], b: &[&str])->Result<Option<Expression>, ExpressionParseError>{
+        let knowledge_base = |labels: &[&str]|->Result<KnowledgeBase, ExpressionParseError>{
+            let mut facts = Vec::new();
+            for label in labels {
+                facts.extend(self.expression_set.entry(label)?.facts().iter().cloned());
+            }
+            Ok(KnowledgeBase::from_facts(facts))
+        };
+        Ok(knowledge_base(a)?.interpolant(&knowledge_base(b)?))
+    }
+    /// :interpolate rule1, rule2 | rule3
+    pub(super) fn parse_interpolant(&self, input: &str)->Result<String, ExpressionParseError>{
+        let Some((a, b)) = input.split_once('|') else {return Err(ExpressionParseError::General)};
+        fn labels(labels: &str)->Vec<&str>{
+            labels.split(',').map(str::trim).filter(|label|!label.is_empty()).collect()
+        }
+        Ok(match self.interpolant(&labels(a), &labels(b))? {
+            Some(interpolant) => {
+                let simplified = Rewriter::algebraic().rewrite(interpolant.node_owned(), None);
+                format!("{}\n\n", ExpressionPrinter::new(&self.variable_names, self.style).print(&simplified))
+            },
+            None => "Both sides hold together, there is no interpolant\n\n".to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::test_helpers::{holds, random_facts, Random};
+
+    /// A follows the interpolant, the interpolant contradicts B and only has shared variables
+    #[test]
+    fn interpolants_separate_the_sides(){
+        let mut random = Random(0x5851F42D4C957F2D);
+        let variables = 6u32;
+        let vars_of = |facts: &[KnowledgeBaseFact]|facts.iter().flat_map(|fact|fact.literals()).map(|literal|*literal.var()).collect::<HashSet<Variable>>();
+
+        let mut refuted = 0;
+        for _ in 0..500 {
+            //a uses the low variables and b the high ones so some are shared and some aren't
+            let count = 1 + random.below(8);
+            let a = random_facts(&mut random, count, 0..4);
+            let count = 1 + random.below(8);
+            let b = random_facts(&mut random, count, 2..6);
+            let together = (0..1u32 << variables).any(|assignment|holds(&a, assignment) && holds(&b, assignment));
+            let a_kb = KnowledgeBase::from_facts(a.clone());
+            let Some(interpolant) = a_kb.interpolant(&KnowledgeBase::from_facts(b.clone())) else {
+                assert!(together);
+                continue;
+            };
+            assert!(!together);
+            refuted += 1;
+
+            let interpolant = KnowledgeBase::from_expression(interpolant);
+            let shared: HashSet<Variable> = vars_of(&a).intersection(&vars_of(&b)).cloned().collect();
+            assert!(vars_of(interpolant.facts()).is_subset(&shared));
+            for assignment in 0..1u32 << variables {
+                assert!(!holds(&a, assignment) || holds(interpolant.facts(), assignment));
+                assert!(!holds(interpolant.facts(), assignment) || !holds(&b, assignment));
+            }
+        }
+        assert!(refuted > 50);
+    }
+}
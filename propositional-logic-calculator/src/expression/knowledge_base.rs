@@ -1,6 +1,5 @@
-use std::collections::{HashMap, HashSet};
-use std::hash::Hash;
-use super::{expression::ExpressionNode, variable::Variable, Expression, VariableNames};
+use std::collections::HashSet;
+use super::{dag::{DagCnfBuilder, ExpressionDag}, expression::ExpressionNode, probing::{ProbeBudget, Prober}, simplifier::{SimplifyStep, Simplifier}, variable::Variable, Expression, VariableNames};
 
 /// Empty KB is tautology
 #[derive(Debug)]
@@ -8,37 +7,60 @@ pub struct KnowledgeBase{
     facts: Vec<KnowledgeBaseFact>
 }
 impl KnowledgeBase{
-    pub(super) fn tautology()->Self{
-        Self{facts: Vec::new()}
+    pub fn tautology()->Self{
+        Self::from_facts(Vec::new())
     }
-    fn facts(&self)->&Vec<KnowledgeBaseFact>{
+    pub fn from_facts(facts: Vec<KnowledgeBaseFact>)->Self{
+        Self{facts}
+    }
+    pub fn facts(&self)->&Vec<KnowledgeBaseFact>{
         &self.facts
     }
-    pub(super) fn from_expression(expression: Expression)->Self{
+    /// Converts through a shared dag so repeated subterms are only converted once
+    pub fn from_expression(expression: Expression)->Self{
+        let mut dag = ExpressionDag::new();
+        let root = dag.add(expression.node());
+        let facts = DagCnfBuilder::new(&dag).facts(root);
+        Self{facts: facts.as_ref().clone()}
+    }
+    /**
+        Converts like from_expression, but an or over a subterm used more than once
+        or one that would make too many facts gets a fresh variable instead of being distributed
+
+        Every model of the expression has exactly one model here with the same values for its variables.
+        Fresh variables start at first_fresh, or after the highest variable in the expression if that is higher,
+        and the first one left unused is returned too.
+    */
+    pub fn from_expression_defined(expression: Expression, first_fresh: u32)->(Self, u32){
+        let mut dag = ExpressionDag::new();
+        let root = dag.add(expression.node());
+        let mut builder = DagCnfBuilder::defining(&dag, root, first_fresh.max(dag.first_unused()));
+        let mut facts = builder.facts(root).as_ref().clone();
+        let (definitions, next_fresh) = builder.definitions();
+        facts.extend(definitions);
+        (Self{facts}, next_fresh)
+    }
+    /// Converts by rewriting the tree, copying subterms as it goes
+    /// Kept to compare against
+    pub(super) fn from_expression_tree(expression: Expression)->Self{
         KnoweldgeBaseBuilder::from_expression(expression)
     }
-    pub(super) fn combine(&mut self, other: KnowledgeBase){
-        self.facts.extend(other.facts.into_iter());
+    /// Builds from a node that is already an and of ors, remembering the fact each or became
+    pub(super) fn from_cnf_tree_explained(cnf_node: ExpressionNode)->(Self, Vec<BuildStep>){
+        KnoweldgeBaseBuilder::build(cnf_node, Some(Vec::new()))
+    }
+    pub fn combine(&mut self, other: KnowledgeBase){
+        self.facts.extend(other.facts);
     }
-    fn push_fact(&mut self, fact: KnowledgeBaseFact){
+    pub fn push_fact(&mut self, fact: KnowledgeBaseFact){
         self.facts.push(fact);
     }
     pub(super) fn display(&self, vars: &VariableNames)->String{
         let mut out: String = String::new();
 
         for fact in self.facts(){
-            out.push('[');
-            for (i, literal) in fact.literals().iter().enumerate(){
-                let Some(var_name) = vars.get_name_from_variable(&literal.var()) else {unreachable!()};
-                if literal.not() {
-                    out.push('!');
-                }
-                out.push_str(format!("{}", var_name).as_str());
-                if i != fact.literals().len().saturating_sub(1) {
-                    out.push_str(", ");
-                }
-            }
-            out.push_str("]\n");
+            out.push_str(&vars.display_fact(fact));
+            out.push('\n');
         }
         out
     }
@@ -56,137 +78,145 @@ impl KnowledgeBase{
         [x, !x] becomes empty
 
 
-        !!! Still missing
-        !!! Leo says this is impossible according to his professor (The guy who like invented SAT solvers or something)
-        [x, z], [!x, y] [!z, y] becomes [x, z], [y]
-        For this we need a to just add [!y] to a KB and check for contradictions
+        Implied facts like
+        [x, z], [!x, y], [!z, y] becomes [x, z], [y]
+        need probing, see simplify_with_probing
     */
-    pub(super) fn simplify(&mut self){
-
-        let mut run_again = true;
-
-        while run_again {
-            run_again = false;
-
-            let mut out = Vec::new();
-            let mut units = HashSet::new();
-
-            //remove superset & duplicates & tautologies
-            for fact_a in self.facts.iter(){
-
-                if out.contains(fact_a) || fact_a.tautology() {
-                    run_again = true;
-                    continue;
-                }
-
-                if !self.facts
-                    .iter()
-                    .any(|fact_b|{
-                        fact_b.is_subset(fact_a) && !fact_a.is_subset(fact_b)
-                    })
-                {
-                    if let Some(literal) = fact_a.is_unit() {
-                        units.insert(literal);
-                    }
-                    out.push(fact_a.clone());
-                }else{
-                    run_again = true;
-                }
-            }
+    pub fn simplify(&mut self){
+        self.facts = Simplifier::new(std::mem::take(&mut self.facts)).run();
+    }
+    /// simplify, returning every rule that fired
+    pub(super) fn simplify_explained(&mut self)->Vec<SimplifyStep>{
+        let (facts, steps) = Simplifier::explained(std::mem::take(&mut self.facts)).run_explained();
+        self.facts = facts;
+        steps
+    }
+    /// simplify, then probe for implied facts and simplify again
+    /// until probing finds nothing new or the budget runs out
+    pub(super) fn simplify_with_probing(&mut self, budget: &ProbeBudget){
+        self.simplify();
 
-            self.facts = out
+        let mut budget = *budget;
+        while budget.probes > 0 {
+            let mut prober = Prober::new(&self.facts, &budget);
+            let derived: Vec<KnowledgeBaseFact> = prober.derive()
                 .into_iter()
-                .map(|mut fact|{
-                    if fact.filter_negative_literals(&units) {
-                        run_again = true;
-                    }
-                    fact
-                })
+                .filter(|fact|!self.facts.iter().any(|existing|existing.is_subset(fact)))
                 .collect();
+            budget.probes = prober.remaining();
+
+            if derived.is_empty() {
+                break;
+            }
+            self.facts.extend(derived);
+            self.simplify();
         }
     }
 }
 
 
 /// empty fact is contradiction
-#[derive(Debug, Clone, Eq)]
-struct KnowledgeBaseFact{
-    set: HashSet<KnowledgeBaseLiteral>
+/// Literals are kept sorted and without duplicates so equal facts are identical
+/// The signature has a bit set for every literal, if a's bits are not all in b then a can't be a subset of b
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct KnowledgeBaseFact{
+    literals: Vec<KnowledgeBaseLiteral>,
+    signature: u64
 }
 impl KnowledgeBaseFact{
-    fn new(set: HashSet<KnowledgeBaseLiteral>)->Self{
-        Self{set}
+    pub fn new(mut literals: Vec<KnowledgeBaseLiteral>)->Self{
+        literals.sort();
+        literals.dedup();
+        let signature = Self::signature_of(&literals);
+        Self{literals, signature}
+    }
+    fn signature_of(literals: &[KnowledgeBaseLiteral])->u64{
+        literals
+            .iter()
+            .fold(0, |signature, literal|signature | 1 << ((literal.var().index() * 2 + literal.not() as usize) % 64))
     }
-    pub(super) fn literals(&self)->&HashSet<KnowledgeBaseLiteral>{
-        &self.set
+    pub fn literals(&self)->&Vec<KnowledgeBaseLiteral>{
+        &self.literals
     }
-    fn contains(&self, literal: &KnowledgeBaseLiteral)->bool{
-        self.set.contains(literal)
+    pub fn len(&self)->usize{
+        self.literals.len()
     }
-    fn is_subset(&self, other: &KnowledgeBaseFact)->bool{
-        self.set.is_subset(&other.set)
+    /// The empty fact, a contradiction
+    pub fn is_empty(&self)->bool{
+        self.literals.is_empty()
     }
-    fn tautology(&self) -> bool {
-        let mut seen = HashSet::new();
-        for lit in &self.set {
-            if seen.contains(&lit.negated()) { return true; }
-            seen.insert(lit);
+    pub fn contains(&self, literal: &KnowledgeBaseLiteral)->bool{
+        self.literals.binary_search(literal).is_ok()
+    }
+    pub(super) fn is_subset(&self, other: &KnowledgeBaseFact)->bool{
+        if self.len() > other.len() || self.signature & !other.signature != 0 {
+            return false;
         }
-        false
+        //both are sorted so walk through them together
+        let mut others = other.literals.iter();
+        self.literals
+            .iter()
+            .all(|literal|others.any(|other|other == literal))
+    }
+    pub(super) fn tautology(&self) -> bool {
+        self.literals
+            .windows(2)
+            .any(|pair|pair[0].var() == pair[1].var())
     }
-    fn contradiction(&self)->bool{
-        self.set.is_empty()
+    pub(super) fn contradiction(&self)->bool{
+        self.literals.is_empty()
     }
-    fn filter_literal(&mut self, literal: &KnowledgeBaseLiteral){
-        self.set.retain(|l|l!=literal);
+    pub(super) fn filter_literal(&mut self, literal: &KnowledgeBaseLiteral){
+        self.literals.retain(|l|l!=literal);
+        self.signature = Self::signature_of(&self.literals);
     }
     /// if any element was removed then true
-    fn filter_negative_literals(&mut self, literals: &HashSet<&KnowledgeBaseLiteral>)->bool{
-        let mut changed = false;
-        self.set.retain(|l|{
-            if !literals.contains(&&l.negated()) {
-                true
-            }else{
-                changed = true;
-                false
-            }
-        });
-        changed
+    pub(super) fn filter_negative_literals(&mut self, literals: &HashSet<&KnowledgeBaseLiteral>)->bool{
+        let len = self.literals.len();
+        self.literals.retain(|l|!literals.contains(&&l.negated()));
+        self.signature = Self::signature_of(&self.literals);
+        len != self.literals.len()
+    }
+    /// Makes literal true
+    /// Removes every fact containing it and removes its negation from the rest
+    pub(super) fn assign(facts: Vec<KnowledgeBaseFact>, literal: &KnowledgeBaseLiteral)->Vec<KnowledgeBaseFact>{
+        let negated = literal.negated();
+        facts
+            .into_iter()
+            .filter(|fact|!fact.contains(literal))
+            .map(|mut fact|{
+                fact.filter_literal(&negated);
+                fact
+            })
+            .collect()
     }
-    fn is_unit(&self)->Option<&KnowledgeBaseLiteral>{
-        if self.set.len() != 1 {
+    pub(super) fn is_unit(&self)->Option<&KnowledgeBaseLiteral>{
+        if self.literals.len() != 1 {
             None
         } else {
-            self.set.iter().next()
+            self.literals.first()
         }
     }
 }
-impl PartialEq for KnowledgeBaseFact{
-    fn eq(&self, other: &Self) -> bool {
-        self.is_subset(other) && other.is_subset(self)
-    }
-}
 
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash)]
-struct KnowledgeBaseLiteral{
-    not: bool,
-    var: Variable
+/// Sorted by variable first so literals of the same variable end up next to each other
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct KnowledgeBaseLiteral{
+    var: Variable,
+    not: bool
 }
 impl KnowledgeBaseLiteral{
-    fn new(not: bool, var: Variable)->Self{
+    pub fn new(not: bool, var: Variable)->Self{
         Self{not, var}
     }
-    pub(super) fn not(&self)->bool{
+    pub fn not(&self)->bool{
         self.not
     }
-    pub(super) fn var(&self)->&Variable{
+    pub fn var(&self)->&Variable{
         &self.var
     }
-    fn complement_of(&self, other: &KnowledgeBaseLiteral)->bool{
-        self.not == !other.not && self.var == other.var
-    }
-    fn negated(&self)->Self{
+    pub fn negated(&self)->Self{
         let mut x = self.clone();
         x.not = !x.not;
         x
@@ -194,75 +224,110 @@ impl KnowledgeBaseLiteral{
 }
 
 
+/// One or of the cnf tree and the fact it became
+/// None if it had a true constant in it and was left out
+#[derive(Debug, Clone)]
+pub(super) struct BuildStep{
+    pub(super) clause: Box<ExpressionNode>,
+    pub(super) fact: Option<KnowledgeBaseFact>
+}
+
 struct KnoweldgeBaseBuilder{
     state: KnowledgeBaseFactBuilder,
-    base: KnowledgeBase
+    base: KnowledgeBase,
+    trace: Option<Vec<BuildStep>>
 }
 impl KnoweldgeBaseBuilder{
     fn from_expression(expression: Expression)->KnowledgeBase{
-        let cnf_node = expression.node_owned().pushdown_not_recursive().distribute_or_recursive();
-        
+        let cnf_node = expression.node_owned().lower_recursive(None).pushdown_not_recursive(None).distribute_or_recursive(None);
+        Self::build(*cnf_node, None).0
+    }
+    fn build(cnf_node: ExpressionNode, trace: Option<Vec<BuildStep>>)->(KnowledgeBase, Vec<BuildStep>){
         let mut builder = Self{
             state: KnowledgeBaseFactBuilder::None,
             base: KnowledgeBase {
                 facts: Vec::new()
-            }
+            },
+            trace
         };
 
-        builder.from_expression_recursive(cnf_node);
-        builder.base
+        builder.push_expression_recursive(cnf_node);
+        (builder.base, builder.trace.unwrap_or_default())
+    }
+    fn push_expression_recursive(&mut self, expr: ExpressionNode){
+        //everything below an and at the top is one fact
+        let starts_fact = self.trace.is_some()
+            && matches!(self.state, KnowledgeBaseFactBuilder::None)
+            && !matches!(expr, ExpressionNode::And(..));
+        if !starts_fact {
+            return self.push_node(expr);
+        }
+
+        let clause = Box::new(expr.clone());
+        let len = self.base.facts.len();
+        self.push_node(expr);
+        let fact = self.base.facts[len..].first().cloned();
+        if let Some(trace) = &mut self.trace {
+            trace.push(BuildStep{clause, fact});
+        }
     }
-    fn from_expression_recursive(&mut self, expr: Box<ExpressionNode>){
-        match *expr {
+    fn push_node(&mut self, expr: ExpressionNode){
+        match expr {
             ExpressionNode::And(a, b) => {
-                self.from_expression_recursive(a);
-                self.from_expression_recursive(b);
+                self.push_expression_recursive(*a);
+                self.push_expression_recursive(*b);
             },
             ExpressionNode::Or(a, b) => {
-                let already_in_fact = matches!(self.state, KnowledgeBaseFactBuilder::Fact(_));
+                let already_in_fact = !matches!(self.state, KnowledgeBaseFactBuilder::None);
                 if !already_in_fact{
-                    self.state = KnowledgeBaseFactBuilder::Fact(HashSet::new());
+                    self.state = KnowledgeBaseFactBuilder::Fact(Vec::new());
                 }
                 
-                self.from_expression_recursive(a);
-                self.from_expression_recursive(b);
+                self.push_expression_recursive(*a);
+                self.push_expression_recursive(*b);
 
                 if !already_in_fact {
-                    let KnowledgeBaseFactBuilder::Fact(fact) = &mut self.state else {panic!()};
-                    self.base.push_fact(KnowledgeBaseFact::new(fact.clone()));
+                    if let KnowledgeBaseFactBuilder::Fact(fact) = &mut self.state {
+                        self.base.push_fact(KnowledgeBaseFact::new(fact.clone()));
+                    }
                     self.state = KnowledgeBaseFactBuilder::None;
                 }
             },
-            ExpressionNode::Variable(variable) => {
-                let new_fact = KnowledgeBaseLiteral::new(false, variable);
-                if let KnowledgeBaseFactBuilder::Fact(fact) = &mut self.state{
-                    fact.insert(new_fact);
-                }else{
-                    let mut fact = HashSet::new();
-                    fact.insert(new_fact);
-                    self.base.push_fact(KnowledgeBaseFact::new(fact));
+            ExpressionNode::Constant(true) => {
+                if !matches!(self.state, KnowledgeBaseFactBuilder::None) {
+                    self.state = KnowledgeBaseFactBuilder::Satisfied;
+                }
+            },
+            ExpressionNode::Constant(false) => {
+                if matches!(self.state, KnowledgeBaseFactBuilder::None) {
+                    self.base.push_fact(KnowledgeBaseFact::new(Vec::new()));
                 }
             },
+            ExpressionNode::Variable(variable) => {
+                self.push_literal(KnowledgeBaseLiteral::new(false, variable));
+            },
             ExpressionNode::Not(a) => {
 
                 let ExpressionNode::Variable(variable) = *a else {panic!()};
-                let new_fact = KnowledgeBaseLiteral::new(true, variable);
-
-                if let KnowledgeBaseFactBuilder::Fact(fact) = &mut self.state{
-                    fact.insert(new_fact);
-                }else{
-                    let mut fact = HashSet::new();
-                    fact.insert(new_fact);
-                    self.base.push_fact(KnowledgeBaseFact::new(fact));
-                }
+                self.push_literal(KnowledgeBaseLiteral::new(true, variable));
             },
+            _ => unreachable!("lowered before building"),
+        }
+    }
+    fn push_literal(&mut self, new_fact: KnowledgeBaseLiteral){
+        match &mut self.state {
+            KnowledgeBaseFactBuilder::Fact(fact) => fact.push(new_fact),
+            KnowledgeBaseFactBuilder::Satisfied => {},
+            KnowledgeBaseFactBuilder::None => self.base.push_fact(KnowledgeBaseFact::new(vec![new_fact])),
         }
     }
 }
 
 #[derive(Default)]
 enum KnowledgeBaseFactBuilder{
-    Fact(HashSet<KnowledgeBaseLiteral>),
+    Fact(Vec<KnowledgeBaseLiteral>),
+    /// The fact has a true constant in it so it is left out
+    Satisfied,
     #[default]
     None
 }
\ No newline at end of file
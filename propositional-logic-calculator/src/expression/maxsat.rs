@@ -0,0 +1,207 @@
+use super::{knowledge_base::{KnowledgeBaseFact, KnowledgeBaseLiteral}, solver::{Model, Solver}, variable::Variable, KnowledgeBase, VariableNames};
+
+/// The cheapest model, what it costs and the soft expressions it breaks
+#[derive(Debug, Clone)]
+pub struct MaxSatSolution{
+    model: Model,
+    cost: u64,
+    broken: Vec<String>
+}
+impl MaxSatSolution{
+    pub(super) fn new(model: Model, cost: u64, broken: Vec<String>)->Self{
+        Self{model, cost, broken}
+    }
+    pub fn model(&self)->&Model{
+        &self.model
+    }
+    /// The total weight of the broken soft expressions, no model breaks less
+    pub fn cost(&self)->u64{
+        self.cost
+    }
+    /// Labels of the soft expressions that don't hold in the model
+    pub fn broken(&self)->&[String]{
+        &self.broken
+    }
+    pub(super) fn display(&self, vars: &VariableNames)->String{
+        let mut out = if self.broken.is_empty() {
+            "Every soft expression holds\n".to_string()
+        }else{
+            format!("Broken: {}\nTotal weight broken: {}\n", self.broken.join(", "), self.cost)
+        };
+        out.push_str(&self.model.display(vars));
+        out
+    }
+}
+
+/// A soft expression as the solver sees it, one of these is assumed each round
+struct SoftCopy{
+    original: usize,
+    weight: u64,
+    //assumed true to make the copy's facts hold
+    selector: Variable,
+    //while false the copy is retired
+    active: bool
+}
+
+/**
+    Weighted MaxSAT, core guided (WPM1, the weighted version of Fu and Malik's algorithm)
+
+    Every soft expression gets a selector variable, its facts are added with !selector
+    so assuming the selector makes it hold and leaving it out makes it free.
+    All selectors are assumed and the solver either finds a model or the selectors that can't all hold, a core.
+    The cheapest weight in the core is paid, and every soft expression in it gets a copy
+    that a new blocking variable can switch off, with exactly one of the core's blocking variables true.
+    An expression that weighs more than what was paid keeps a copy with the rest of its weight.
+    The first model found breaks the least weight possible.
+
+    One solver is used the whole way, retired copies just stop being assumed.
+*/
+pub(super) struct MaxSat{
+    hard: KnowledgeBase,
+    soft: Vec<(Vec<KnowledgeBaseFact>, u64)>
+}
+impl MaxSat{
+    pub(super) fn new(hard: KnowledgeBase)->Self{
+        Self{hard, soft: Vec::new()}
+    }
+    pub(super) fn add_soft(&mut self, facts: Vec<KnowledgeBaseFact>, weight: u64){
+        self.soft.push((facts, weight));
+    }
+    /// The cheapest model, its cost and the indices of the soft expressions it breaks
+    /// None if the hard facts contradict each other
+    pub(super) fn solve(&self)->Option<(Model, u64, Vec<usize>)>{
+        let first_fresh = self.hard.facts()
+            .iter()
+            .chain(self.soft.iter().flat_map(|(facts, _)|facts))
+            .flat_map(|fact|fact.literals())
+            .map(|literal|literal.var().index() as u32 + 1)
+            .max()
+            .unwrap_or(0);
+        let mut next_fresh = first_fresh;
+        let mut fresh = ||{
+            next_fresh += 1;
+            Variable::new(next_fresh - 1)
+        };
+
+        let mut solver = Solver::new(&self.hard);
+        let mut copies = Vec::new();
+        let add_copy = |solver: &mut Solver, copies: &mut Vec<SoftCopy>, original: usize, weight: u64, blocking: &[Variable], selector: Variable|{
+            let relax: Vec<KnowledgeBaseLiteral> = blocking
+                .iter()
+                .map(|var|KnowledgeBaseLiteral::new(false, *var))
+                .chain(std::iter::once(KnowledgeBaseLiteral::new(true, selector)))
+                .collect();
+            for fact in self.soft[original].0.iter() {
+                solver.add_fact(&KnowledgeBaseFact::new(fact.literals().iter().chain(relax.iter()).cloned().collect()));
+            }
+            copies.push(SoftCopy{original, weight, selector, active: true});
+        };
+        //blocking variables each copy has, by copy
+        let mut blocking: Vec<Vec<Variable>> = Vec::new();
+        for (original, (_, weight)) in self.soft.iter().enumerate() {
+            add_copy(&mut solver, &mut copies, original, *weight, &[], fresh());
+            blocking.push(Vec::new());
+        }
+
+        let mut cost = 0;
+        loop {
+            let assumptions: Vec<KnowledgeBaseLiteral> = copies
+                .iter()
+                .filter(|copy|copy.active)
+                .map(|copy|KnowledgeBaseLiteral::new(false, copy.selector))
+                .collect();
+            if let Some(model) = solver.solve_with_assumptions(&assumptions) {
+                let broken = (0..self.soft.len()).filter(|original|!Self::holds(&self.soft[*original].0, &model)).collect();
+                let mut named = Model::default();
+                for var in (0..first_fresh).map(Variable::new) {
+                    if let Some(value) = model.value(&var) {
+                        named.set(var, value);
+                    }
+                }
+                return Some((named, cost, broken));
+            }
+
+            let core: Vec<usize> = solver.failed_assumptions()
+                .iter()
+                .filter_map(|literal|copies.iter().position(|copy|copy.active && copy.selector == *literal.var()))
+                .collect();
+            //nothing soft was needed for the contradiction
+            if core.is_empty() {
+                return None;
+            }
+            let paid = core.iter().map(|index|copies[*index].weight).min().expect("the core isn't empty");
+            cost += paid;
+
+            let mut core_blocking = Vec::new();
+            for index in core {
+                let block = fresh();
+                core_blocking.push(block);
+                let copy = &mut copies[index];
+                copy.weight -= paid;
+                copy.active = copy.weight > 0;
+                let original = copy.original;
+                let mut relaxed = blocking[index].clone();
+                relaxed.push(block);
+                add_copy(&mut solver, &mut copies, original, paid, &relaxed, fresh());
+                blocking.push(relaxed);
+            }
+
+            //exactly one copy in the core is switched off
+            solver.add_fact(&KnowledgeBaseFact::new(core_blocking.iter().map(|var|KnowledgeBaseLiteral::new(false, *var)).collect()));
+            for (i, a) in core_blocking.iter().enumerate() {
+                for b in core_blocking[i + 1..].iter() {
+                    solver.add_fact(&KnowledgeBaseFact::new(vec![KnowledgeBaseLiteral::new(true, *a), KnowledgeBaseLiteral::new(true, *b)]));
+                }
+            }
+        }
+    }
+    /// The solver drops tautologies so their variables can be missing from the model
+    fn holds(facts: &[KnowledgeBaseFact], model: &Model)->bool{
+        facts.iter().all(|fact|fact.tautology() || fact.literals().iter().any(|literal|model.value(literal.var()) == Some(!literal.not())))
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::test_helpers::{model_of, random_facts, Random};
+
+    /// Checks the cost against trying every assignment
+    #[test]
+    fn matches_brute_force(){
+        let mut random = Random(0x9E3779B97F4A7C15);
+        let variables = 6;
+        for _ in 0..300 {
+            let count = random.below(8);
+            let hard = random_facts(&mut random, count, 0..variables);
+            let mut maxsat = MaxSat::new(KnowledgeBase::from_facts(hard.clone()));
+            let mut soft = Vec::new();
+            for _ in 0..1 + random.below(8) {
+                let count = 1 + random.below(2);
+                let facts = random_facts(&mut random, count, 0..variables);
+                let weight = 1 + random.below(5);
+                maxsat.add_soft(facts.clone(), weight);
+                soft.push((facts, weight));
+            }
+
+            let mut best = None;
+            for assignment in 0..1u32 << variables {
+                let model = model_of(assignment, variables);
+                if !MaxSat::holds(&hard, &model) {
+                    continue;
+                }
+                let cost: u64 = soft.iter().filter(|(facts, _)|!MaxSat::holds(facts, &model)).map(|(_, weight)|weight).sum();
+                best = Some(best.map_or(cost, |best: u64|best.min(cost)));
+            }
+
+            match maxsat.solve() {
+                None => assert_eq!(best, None),
+                Some((model, cost, broken)) => {
+                    assert_eq!(Some(cost), best);
+                    assert!(MaxSat::holds(&hard, &model));
+                    assert_eq!(broken.iter().map(|index|soft[*index].1).sum::<u64>(), cost);
+                },
+            }
+        }
+    }
+}
@@ -1,67 +1,529 @@
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap, HashSet};
 
-use expression::Expression;
-use parser::ExpressionParseError;
-use variable::Variable;
+use backbone::Backbone;
+use dag::ExpressionDag;
+use explain::Explanation;
+use expression_set::{Content, ExpressionSet};
+use model_counter::{ModelCounter, Undefined, VariableWeights};
+use parser::ExpressionParser;
+use maxsat::MaxSat;
+use preprocess::Preprocessor;
+use printer::{ExpressionPrinter, OperatorStyle};
+use probing::ProbeBudget;
 
 mod parser;
 mod variable;
 mod knowledge_base;
+#[allow(clippy::module_inception)]
 mod expression;
+mod model_counter;
+mod solver;
+mod backbone;
+mod probing;
+mod preprocess;
+mod simplifier;
+mod benchmark;
+mod printer;
+mod dag;
+mod rewrite;
+mod explain;
+mod dot;
+mod expression_set;
+mod save;
+mod display;
+mod builder;
+mod puzzle;
+mod cardinality;
+mod examples;
+mod coloring;
+mod maxsat;
+mod optimize;
+mod prime;
+mod forget;
+mod interpolant;
+mod random;
+#[cfg(test)]
+mod test_helpers;
 
-pub use knowledge_base::KnowledgeBase;
+pub use knowledge_base::{KnowledgeBase, KnowledgeBaseFact, KnowledgeBaseLiteral};
+pub use benchmark::{benchmark_cnf, benchmark_examples, benchmark_simplify};
+pub use builder::Var;
+pub use coloring::Graph;
+pub use display::Named;
+pub use examples::{Queens, Sudoku};
+pub use expression::{Expression, ExpressionNode};
+pub use maxsat::MaxSatSolution;
+pub use parser::ExpressionParseError;
+pub use rewrite::{NamedRule, RewriteRule, RewriteStep, Rewriter};
+pub use save::LoadError;
+pub use solver::{Model, Solver};
+pub use variable::Variable;
 
 
 
 pub struct Workspace{
     variable_names: VariableNames,
-    expression_set: ExpressionSet
+    expression_set: ExpressionSet,
+    weights: VariableWeights,
+    probe_budget: ProbeBudget,
+    style: OperatorStyle,
+    //every expression's facts, kept between :assume queries so learned facts are reused
+    solver: Solver,
+    //the variables that are people in a knights and knaves puzzle
+    people: Vec<Variable>,
+    //once there is a count nobody new can join the puzzle
+    counted: bool
+}
+impl Default for Workspace{
+    fn default()->Self{
+        Self::new()
+    }
 }
 impl Workspace{
     pub fn new()->Self{
-        Self { variable_names: VariableNames::new(), expression_set: ExpressionSet::new() }
+        Self { variable_names: VariableNames::new(), expression_set: ExpressionSet::new(), weights: VariableWeights::new(), probe_budget: ProbeBudget::default(), style: OperatorStyle::default(), solver: Solver::new(&KnowledgeBase::tautology()), people: Vec::new(), counted: false }
+    }
+    /// Either an expression or a directive
+    /// anna > josh adds an expression numbered by the order it came in, rule: anna > josh adds it labelled rule
+    /// :retract rule removes the expression labelled rule, :replace rule: anna & josh changes it
+    /// :undo and :redo undo and redo adding, retracting and replacing
+    /// p(anna) = 0.3 sets the probability that anna is true
+    /// p(anna & josh) prints the probability of the query given all expressions
+    /// :backbone prints what every variable must be
+    /// :probe 10000 sets how many literals simplifying may probe looking for implied facts
+    /// :preprocess prints what preprocessing does to the knowledge base
+    /// :solve prints a model
+    /// :print prints every expression
+    /// :style unicode sets how expressions are printed, one of ascii, unicode, latex, words
+    /// :simplify prints every expression simplified, :simplify trace also prints each rule used
+    /// :explain prints every step of turning the expressions into the knowledge base
+    /// :assume anna, !josh prints a model where anna is true and josh is false, or which of those can't both hold
+    /// :save session.txt saves everything to a file, :load session.txt replaces everything with what the file has
+    /// :dot tree, :dot dag or :dot kb prints graphviz, :dot kb out.dot writes it to a file instead
+    /// anna says josh ^ matthew, exactly 2 evil, at least 1 good and at most 3 evil are knights and knaves statements
+    /// :people anna, josh declares people who don't say anything, everyone has to be declared before the first count
+    /// :answer prints who is good and who is evil
+    /// [5] anna > josh is soft, it may be broken for a cost of 5, :maxsat prints the model that breaks the least weight
+    /// minimize count(anna, josh, !matthew) prints a model with as few of those true as possible, maximize count(..) as many
+    /// :forget anna, josh prints what the knowledge base says about everything else, :project anna, josh about only them
+    /// :interpolate rule1, rule2 | rule3 prints what rule1 and rule2 say, over the variables rule3 also has, that contradicts rule3
+    /// :implicates prints the prime implicates of the knowledge base, :implicants its prime implicants, :implicates 500 stops past 500
+    /// What it prints comes back as the output, empty for lines that only change something
+    pub fn parse_line(&mut self, input: &str)->Result<String, ExpressionParseError>{
+        let trimmed = input.trim();
+        if let Some(label) = trimmed.strip_prefix(":retract") {
+            self.expression_set.retract(label.trim())?;
+            self.rebuild_solver();
+            return Ok(String::new());
+        }
+        if let Some(replacement) = trimmed.strip_prefix(":replace") {
+            let Some((label, input)) = replacement.split_once(':') else {return Err(ExpressionParseError::General)};
+            let (content, weight) = self.parse_weighted(input)?;
+            self.counted |= matches!(content, Content::Count(_));
+            self.expression_set.replace(label.trim(), content, weight, &mut self.variable_names)?;
+            self.rebuild_solver();
+            return Ok(String::new());
+        }
+        if trimmed == ":undo" {
+            if !self.expression_set.undo() {
+                return Err(ExpressionParseError::NothingToUndo);
+            }
+            self.rebuild_solver();
+            return Ok(String::new());
+        }
+        if trimmed == ":redo" {
+            if !self.expression_set.redo() {
+                return Err(ExpressionParseError::NothingToRedo);
+            }
+            self.rebuild_solver();
+            return Ok(String::new());
+        }
+        if let Some(path) = trimmed.strip_prefix(":save") {
+            let path = path.trim();
+            self.save(path).map_err(|error|ExpressionParseError::Write{path: path.to_string(), error})?;
+            return Ok(String::new());
+        }
+        if let Some(path) = trimmed.strip_prefix(":load") {
+            let path = path.trim();
+            *self = Self::load(path).map_err(|error|ExpressionParseError::Load{path: path.to_string(), error})?;
+            return Ok(String::new());
+        }
+        if trimmed == ":print" {
+            return Ok(self.display_expressions());
+        }
+        if let Some(style) = trimmed.strip_prefix(":style") {
+            let Some(style) = OperatorStyle::from_name(style.trim()) else {return Err(ExpressionParseError::General)};
+            self.style = style;
+            return Ok(String::new());
+        }
+        if let Some(trace) = trimmed.strip_prefix(":simplify") {
+            return match trace.trim() {
+                "" => Ok(self.display_simplified(false)),
+                "trace" => Ok(self.display_simplified(true)),
+                _ => Err(ExpressionParseError::General),
+            };
+        }
+        if let Some(args) = trimmed.strip_prefix(":dot") {
+            let mut args = args.split_whitespace();
+            let dot = match args.next() {
+                Some("tree") => self.expression_trees_dot(),
+                Some("dag") => self.dag_dot(),
+                Some("kb") => dot::knowledge_base_dot(&self.knowledge_base_from_all_expressions(), &self.variable_names),
+                _ => return Err(ExpressionParseError::General),
+            };
+            let Some(path) = args.next() else {return Ok(format!("{}\n", dot))};
+            std::fs::write(path, dot).map_err(|error|ExpressionParseError::Write{path: path.to_string(), error})?;
+            return Ok(String::new());
+        }
+        if let Some(assumptions) = trimmed.strip_prefix(":assume") {
+            return self.parse_assumptions(assumptions);
+        }
+        if let Some(people) = trimmed.strip_prefix(":people") {
+            for person in people.split(',') {
+                self.declare_person(person.trim())?;
+            }
+            return Ok(String::new());
+        }
+        for (directive, maximize) in [("minimize", false), ("maximize", true)] {
+            if let Some(objective) = trimmed.strip_prefix(directive).filter(|objective|objective.trim_start().starts_with("count(")) {
+                return self.parse_objective(maximize, objective);
+            }
+        }
+        if let Some(vars) = trimmed.strip_prefix(":forget") {
+            return self.parse_forget(vars, false);
+        }
+        if let Some(vars) = trimmed.strip_prefix(":project") {
+            return self.parse_forget(vars, true);
+        }
+        if let Some(partitions) = trimmed.strip_prefix(":interpolate") {
+            return self.parse_interpolant(partitions);
+        }
+        for (directive, implicants) in [(":implicates", false), (":implicants", true)] {
+            if let Some(limit) = trimmed.strip_prefix(directive) {
+                let limit = match limit.trim() {
+                    "" => prime::DEFAULT_LIMIT,
+                    limit => limit.parse().map_err(|_|ExpressionParseError::General)?,
+                };
+                return Ok(if implicants {self.display_prime_implicants(limit)} else {self.display_prime_implicates(limit)});
+            }
+        }
+        match trimmed {
+            ":maxsat" => return Ok(self.display_maxsat()),
+            ":answer" => return Ok(self.display_answer()),
+            ":explain" => return Ok(self.display_explanation()),
+            ":backbone" => return Ok(self.display_backbone()),
+            ":preprocess" => return Ok(self.display_preprocessed()),
+            ":solve" => return Ok(self.display_model()),
+            _ => {},
+        }
+        if let Some(budget) = trimmed.strip_prefix(":probe") {
+            let Ok(probes) = budget.trim().parse() else {return Err(ExpressionParseError::General)};
+            self.probe_budget = ProbeBudget{probes};
+            return Ok(String::new());
+        }
+        if let Some(inner) = trimmed.strip_prefix("p(") {
+            return match inner.split_once('=') {
+                Some((variable, probability)) => self.parse_probability(variable, probability).map(|_|String::new()),
+                None => self.parse_probability_query(inner),
+            };
+        }
+        if let Some((label, input)) = Self::split_label(trimmed) {
+            return self.push_expression(Some(label), input).map(|_|String::new());
+        }
+        self.parse_expression(input).map(|_|String::new())
+    }
+    /// label: expression, labels are made of letters, numbers and _
+    fn split_label(trimmed: &str)->Option<(&str, &str)>{
+        trimmed.split_once(':').filter(|(label, _)|!label.is_empty() && label.chars().all(|c|c.is_alphanumeric() || c == '_'))
+    }
+    pub fn variable_names(&self)->&VariableNames{
+        &self.variable_names
+    }
+    /// A handle for building expressions in code, made if there isn't one with that name yet
+    pub fn var(&mut self, name: &str)->Result<Var, ExpressionParseError>{
+        self.variable_names.var(name)
     }
     pub fn parse_expression(&mut self, input: &str)->Result<(), ExpressionParseError>{
-        match Expression::parse_string_with_variable_names(input, &mut self.variable_names) {
-            Ok(expr) => {
-                self.expression_set.push(expr);
-                Ok(())
-            },
-            Err(e) => {Err(e)},
+        self.push_expression(None, input)
+    }
+    fn push_expression(&mut self, label: Option<&str>, input: &str)->Result<(), ExpressionParseError>{
+        let (content, weight) = self.parse_weighted(input)?;
+        self.push_entry(label, content, weight)
+    }
+    /// [5] at the start makes it soft with a weight of 5
+    fn parse_weighted(&mut self, input: &str)->Result<(Content, Option<u64>), ExpressionParseError>{
+        let Some((weight, input)) = input.trim().strip_prefix('[').and_then(|rest|rest.split_once(']')) else {
+            return Ok((self.parse_input(input)?, None));
+        };
+        let Some(weight) = weight.trim().parse::<u64>().ok().filter(|weight|*weight > 0) else {return Err(ExpressionParseError::InvalidWeight)};
+        Ok((self.parse_input(input)?, Some(weight)))
+    }
+    /// A puzzle statement or an expression
+    fn parse_input(&mut self, input: &str)->Result<Content, ExpressionParseError>{
+        match self.parse_statement(input) {
+            Some(content) => content,
+            None => Expression::parse(input, &mut self.variable_names).map(Content::Expression),
+        }
+    }
+    /// A model of every expression, None if they contradict each other
+    /// Uses the same solver as :assume so what it learned is kept
+    pub fn solve(&mut self)->Option<Model>{
+        let model = self.solver.solve()?;
+        Some(self.named_model(&model))
+    }
+    /// Only the variables with names, the fresh ones from converting are left out
+    fn named_model(&self, model: &Model)->Model{
+        let mut named = Model::default();
+        for var in self.variable_names.variables() {
+            if let Some(value) = model.value(&var) {
+                named.set(var, value);
+            }
+        }
+        named
+    }
+    /// Every fresh variable in kb, they were made converting expressions and have no name
+    fn fresh_variables(&self, kb: &KnowledgeBase)->Vec<Variable>{
+        let named: HashSet<Variable> = self.variable_names.variables().into_iter().collect();
+        let fresh: BTreeSet<Variable> = kb.facts().iter().flat_map(|fact|fact.literals()).map(|literal|*literal.var()).filter(|var|!named.contains(var)).collect();
+        fresh.into_iter().collect()
+    }
+    /// The hard expressions with the fresh variables forgotten,
+    /// which can make as many facts as distributing would have
+    fn named_knowledge_base(&self)->KnowledgeBase{
+        let kb = self.expression_set.knowledge_base();
+        kb.forget(&self.fresh_variables(&kb))
+    }
+    /// Adds an expression built in code, numbered if there is no label
+    /// Its variables have to come from this workspace
+    pub fn add_expression(&mut self, label: Option<&str>, expr: Expression)->Result<(), ExpressionParseError>{
+        self.push_entry(label, Content::Expression(expr), None)
+    }
+    /// Adds an expression that may be broken, for a cost of weight
+    pub fn add_soft_expression(&mut self, label: Option<&str>, expr: Expression, weight: u64)->Result<(), ExpressionParseError>{
+        if weight == 0 {
+            return Err(ExpressionParseError::InvalidWeight);
+        }
+        self.push_entry(label, Content::Expression(expr), Some(weight))
+    }
+    /// Soft expressions stay out of the solver, only :maxsat looks at them
+    fn push_entry(&mut self, label: Option<&str>, content: Content, weight: Option<u64>)->Result<(), ExpressionParseError>{
+        self.counted |= matches!(content, Content::Count(_));
+        let entry = self.expression_set.push(label, content, weight, &mut self.variable_names)?;
+        if entry.weight().is_none() {
+            for fact in entry.facts() {
+                self.solver.add_fact(fact);
+            }
         }
+        Ok(())
     }
+    /// The solver can only add facts, so after anything is taken away it starts again from the cached facts
+    fn rebuild_solver(&mut self){
+        self.solver = Solver::new(&self.expression_set.knowledge_base());
+    }
+    fn parse_probability(&mut self, variable: &str, probability: &str)->Result<(), ExpressionParseError>{
+        let Some(variable) = variable.trim().strip_suffix(')') else {return Err(ExpressionParseError::General)};
+        let expr = Expression::parse(variable, &mut self.variable_names)?;
+        let ExpressionNode::Variable(var) = expr.node() else {return Err(ExpressionParseError::InvalidProbability)};
+
+        let Ok(probability) = probability.trim().parse::<f64>() else {return Err(ExpressionParseError::InvalidProbability)};
+        if !(0.0..=1.0).contains(&probability) {
+            return Err(ExpressionParseError::InvalidProbability);
+        }
+        self.weights.set_probability(*var, probability);
+        Ok(())
+    }
+    fn parse_probability_query(&mut self, query: &str)->Result<String, ExpressionParseError>{
+        let Some(query) = query.trim().strip_suffix(')') else {return Err(ExpressionParseError::General)};
+        let expr = Expression::parse(query, &mut self.variable_names)?;
+
+        let kb = self.knowledge_base_from_all_expressions();
+        let (query_kb, _) = KnowledgeBase::from_expression_defined(expr, self.variable_names.len());
+        //every model has one value for each fresh variable, so they don't change the weight
+        let mut weights = self.weights.clone();
+        for var in self.fresh_variables(&kb).into_iter().chain(self.fresh_variables(&query_kb)) {
+            weights.set_auxiliary(var);
+        }
+        Ok(match ModelCounter::new(&weights).conditional_probability(&kb, &query_kb) {
+            Ok(probability) => format!("P({}) = {}\n", query.trim(), probability),
+            Err(Undefined::Contradiction) => format!("P({}) is undefined, the knowledge base is a contradiction\n", query.trim()),
+            Err(Undefined::ImpossibleEvidence) => format!("P({}) is undefined, the knowledge base has probability 0\n", query.trim()),
+        })
+    }
+    fn parse_assumptions(&mut self, input: &str)->Result<String, ExpressionParseError>{
+        let mut assumptions = Vec::new();
+        for assumption in input.split(',') {
+            let expr = Expression::parse(assumption, &mut self.variable_names)?;
+            let literal = match expr.node() {
+                ExpressionNode::Variable(var) => KnowledgeBaseLiteral::new(false, *var),
+                ExpressionNode::Not(node) => match &**node {
+                    ExpressionNode::Variable(var) => KnowledgeBaseLiteral::new(true, *var),
+                    _ => return Err(ExpressionParseError::General),
+                },
+                _ => return Err(ExpressionParseError::General),
+            };
+            assumptions.push(literal);
+        }
+
+        Ok(match self.solver.solve_with_assumptions(&assumptions) {
+            Some(model) => format!("{}\n", model.display(&self.variable_names)),
+            None if self.solver.failed_assumptions().is_empty() => "Contradiction, the knowledge base has no models\n\n".to_string(),
+            None => {
+                let failed: Vec<String> = self.solver.failed_assumptions().iter().map(|literal|self.variable_names.display_literal(literal)).collect();
+                let all = if failed.len() > 1 {" all"} else {""};
+                format!("No model, {} can't{} be true\n\n", failed.join(", "), all)
+            },
+        })
+    }
+    /// Every hard expression's facts together, simplified and probed
+    /// Parts that would blow up if distributed are given fresh variables, which are named _ and their id
     pub fn knowledge_base_from_all_expressions(&self)->KnowledgeBase{
-        let mut kb = self.expression_set.set.iter()
-            .fold(KnowledgeBase::tautology(), |mut kb, x|{
-                kb.combine(KnowledgeBase::from_expression(x.clone()));
-                kb
-            });
-        println!("Knowledge base complete");
-        kb.simplify();
-        println!("Knowledge base simplified");
+        let mut kb = self.expression_set.knowledge_base();
+        kb.simplify_with_probing(&self.probe_budget);
         kb
     }
-    pub fn print_knowledge_base_from_all_expressions(&self){
-        println!("{}", self.display_knowledge_base())
+    /// Every expression with its label, a line each
+    pub fn display_expressions(&self)->String{
+        let printer = ExpressionPrinter::new(&self.variable_names, self.style);
+        let mut out = String::new();
+        for entry in self.expression_set.entries() {
+            match entry.weight() {
+                Some(weight) => out.push_str(&format!("{}: [{}] {}\n", entry.label(), weight, entry.display(&printer))),
+                None => out.push_str(&format!("{}: {}\n", entry.label(), entry.display(&printer))),
+            }
+        }
+        out
+    }
+    /// The model of the hard expressions that breaks the least weight of soft ones
+    /// None if the hard expressions contradict each other
+    pub fn solve_maxsat(&self)->Option<MaxSatSolution>{
+        let mut maxsat = MaxSat::new(self.expression_set.knowledge_base());
+        let soft = self.expression_set.soft();
+        for entry in soft.iter() {
+            maxsat.add_soft(entry.facts().clone(), entry.weight().expect("soft entries have a weight"));
+        }
+        let (model, cost, broken) = maxsat.solve()?;
+        let broken = broken.into_iter().map(|index|soft[index].label().to_string()).collect();
+        Some(MaxSatSolution::new(self.named_model(&model), cost, broken))
+    }
+    pub fn display_maxsat(&self)->String{
+        match self.solve_maxsat() {
+            Some(solution) => format!("{}\n", solution.display(&self.variable_names)),
+            None => "Contradiction, the hard expressions have no models\n\n".to_string(),
+        }
+    }
+    /// Every expression simplified, with trace each rule used before it
+    pub fn display_simplified(&self, trace: bool)->String{
+        let printer = ExpressionPrinter::new(&self.variable_names, self.style);
+        let rewriter = Rewriter::algebraic();
+        let mut out = String::new();
+        for expr in self.expression_set.expressions() {
+            let mut steps: Vec<RewriteStep> = Vec::new();
+            let simplified = rewriter.rewrite(expr.node().clone().into(), trace.then_some(&mut steps));
+            for step in steps {
+                out.push_str(&format!("  {}: {} becomes {}\n", step.rule, printer.print(&step.before), printer.print(&step.after)));
+            }
+            out.push_str(&format!("{}\n", printer.print(&simplified)));
+        }
+        out
+    }
+    fn expression_trees_dot(&self)->String{
+        let nodes: Vec<&ExpressionNode> = self.expression_set.expressions().into_iter().map(|expr|expr.node()).collect();
+        dot::expression_trees_dot(&nodes, &self.variable_names, self.style)
+    }
+    /// Every expression in one dag so shared subterms are shared between expressions too
+    fn dag_dot(&self)->String{
+        let mut dag = ExpressionDag::new();
+        let roots: Vec<_> = self.expression_set.expressions().into_iter().map(|expr|dag.add(expr.node())).collect();
+        dot::dag_dot(&dag, &roots, &self.variable_names, self.style)
+    }
+    pub fn display_explanation(&self)->String{
+        format!("{}\n", Explanation::new(&self.expression_set.expressions()).display(&self.variable_names, self.style))
     }
-    fn display_knowledge_base(&self) -> String {
-        self.knowledge_base_from_all_expressions().display(&self.variable_names)
+    pub fn display_knowledge_base(&self)->String{
+        format!("{}\n", self.knowledge_base_from_all_expressions().display(&self.variable_names))
+    }
+    pub fn display_backbone(&self)->String{
+        let kb = self.knowledge_base_from_all_expressions();
+        format!("{}\n", Backbone::from_knowledge_base(&kb, &self.variable_names.variables()).display(&self.variable_names))
+    }
+    /// What preprocessing did, then the knowledge base it left
+    pub fn display_preprocessed(&self)->String{
+        let mut preprocessor = Preprocessor::new(&self.knowledge_base_from_all_expressions());
+        preprocessor.run();
+        format!("{}\n{}\n", preprocessor.display(&self.variable_names), preprocessor.knowledge_base().display(&self.variable_names))
+    }
+    /// Solves the preprocessed knowledge base then reconstructs a model of the original
+    pub fn display_model(&self)->String{
+        let mut preprocessor = Preprocessor::new(&self.knowledge_base_from_all_expressions());
+        preprocessor.run();
+        match Solver::new(&preprocessor.knowledge_base()).solve() {
+            Some(model) => format!("{}\n", preprocessor.reconstruct(&model).display(&self.variable_names)),
+            None => "Contradiction, the knowledge base has no models\n\n".to_string(),
+        }
     }
 }
 
 
-pub(super) struct VariableNames{
+/// The name of every variable
+/// Ids are handed out in the order names are first seen, starting at 0
+/// Fresh variables made while converting to facts get ids from the same count,
+/// their names start with _ which the parser takes out of anything typed, so they can't be looked up
+#[derive(Debug, Default)]
+pub struct VariableNames{
     names: HashMap<String, Variable>
 }
 impl VariableNames{
-    fn new()->Self{
+    pub fn new()->Self{
         Self { names: HashMap::new() }
     }
-    fn push_names(&mut self, names: HashMap<String, Variable>){
-        self.names.extend(names.into_iter());
+    /// How many ids have been handed out, named or fresh
+    pub(super) fn len(&self)->u32{
+        self.names.len() as u32
     }
-    fn names_map_owned(self)->HashMap<String, Variable>{
-        self.names
+    /// Hands out every id below until that isn't yet as a fresh variable
+    pub(super) fn reserve(&mut self, until: u32){
+        for id in self.len()..until {
+            self.names.insert(format!("_{}", id), Variable::new(id));
+        }
+    }
+    /// The variable called name, made if there isn't one yet
+    /// Names are read like the parser reads them so they are lowercase and can't contain operators
+    pub fn variable(&mut self, name: &str)->Result<Variable, ExpressionParseError>{
+        let Some(name) = Self::canonical_name(name) else {return Err(ExpressionParseError::General)};
+        let len = self.names.len() as u32;
+        Ok(*self.names.entry(name).or_insert_with(||Variable::new(len)))
+    }
+    /// The variable called name, without making one if there isn't
+    pub fn find(&self, name: &str)->Result<Variable, ExpressionParseError>{
+        Self::canonical_name(name)
+            .and_then(|canonical|self.names.get(&canonical).copied())
+            .ok_or_else(||ExpressionParseError::UnknownVariable(name.to_string()))
+    }
+    /// variable as a Var to build expressions with
+    pub fn var(&mut self, name: &str)->Result<Var, ExpressionParseError>{
+        self.variable(name).map(Var::new)
+    }
+    /// None for fresh variables
+    pub fn name(&self, var: &Variable)->Option<&str>{
+        self.get_name_from_variable(var).map(|name|name.as_str()).filter(|name|!name.starts_with('_'))
+    }
+    /// Sorted by when they were first seen, without the fresh ones
+    pub fn variables(&self)->Vec<Variable>{
+        let mut variables: Vec<Variable> = self.names.iter().filter(|(name, _)|!name.starts_with('_')).map(|(_, var)|*var).collect();
+        variables.sort();
+        variables
+    }
+    /// The name the parser gives it, so "Q [1] [2]" is q[1][2], None if it isn't a single variable
+    fn canonical_name(name: &str)->Option<String>{
+        let mut scratch = HashMap::new();
+        match ExpressionParser::parse_string(name, &mut scratch).as_deref() {
+            Ok(ExpressionNode::Variable(_)) => scratch.into_keys().next(),
+            _ => None,
+        }
+    }
+    /// Already written the way the parser would write it
+    fn is_name(name: &str)->bool{
+        Self::canonical_name(name).is_some_and(|canonical|canonical == name)
     }
     fn get_name_from_variable(&self, var: &Variable)->Option<&String>{
         self.names
@@ -69,19 +531,19 @@ impl VariableNames{
             .find(|(_, v)|**v==*var)
             .map(|(s,_)|s)
     }
-    fn get_variable(&self, var: &String)->Option<&Variable>{
-        self.names.get(var)
+    fn display_variable(&self, var: &Variable)->String{
+        let Some(var_name) = self.get_name_from_variable(var) else {unreachable!()};
+        var_name.clone()
     }
-}
-
-pub(super) struct ExpressionSet{
-    set: Vec<Expression>
-}
-impl ExpressionSet{
-    pub(super) fn new()->Self{
-        Self{set: Vec::new()}
+    fn display_literal(&self, literal: &KnowledgeBaseLiteral)->String{
+        if literal.not() {
+            format!("!{}", self.display_variable(literal.var()))
+        }else{
+            self.display_variable(literal.var())
+        }
     }
-    pub(super) fn push(&mut self, expr: Expression){
-        self.set.push(expr);
+    fn display_fact(&self, fact: &KnowledgeBaseFact)->String{
+        let literals: Vec<String> = fact.literals().iter().map(|literal|self.display_literal(literal)).collect();
+        format!("[{}]", literals.join(", "))
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,250 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{knowledge_base::{KnowledgeBaseFact, KnowledgeBaseLiteral}, variable::Variable, KnowledgeBase};
+
+/// Probability that each variable is true
+/// Variables without a probability are treated as a coin flip (0.5)
+/// Auxiliary variables weigh 1 both ways, they are fresh variables with one value in every model
+#[derive(Debug, Clone, Default)]
+pub(super) struct VariableWeights{
+    probabilities: HashMap<Variable, f64>,
+    auxiliary: HashSet<Variable>
+}
+impl VariableWeights{
+    pub(super) fn new()->Self{
+        Self::default()
+    }
+    pub(super) fn set_auxiliary(&mut self, var: Variable){
+        self.auxiliary.insert(var);
+    }
+    pub(super) fn set_probability(&mut self, var: Variable, probability: f64){
+        self.probabilities.insert(var, probability);
+    }
+    pub(super) fn probability(&self, var: &Variable)->f64{
+        self.probabilities.get(var).copied().unwrap_or(0.5)
+    }
+    /// Only the ones that were set, sorted by variable
+    pub(super) fn probabilities(&self)->Vec<(Variable, f64)>{
+        let mut probabilities: Vec<(Variable, f64)> = self.probabilities.iter().map(|(var, p)|(*var, *p)).collect();
+        probabilities.sort_by_key(|a| a.0);
+        probabilities
+    }
+    fn literal_weight(&self, literal: &KnowledgeBaseLiteral)->f64{
+        if self.auxiliary.contains(literal.var()) {
+            return 1.0;
+        }
+        let p = self.probability(literal.var());
+        if literal.not() {1.0 - p} else {p}
+    }
+}
+
+/// Why P(query | kb) has no value
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum Undefined{
+    /// The kb has no models
+    Contradiction,
+    /// The kb has models but each one has a literal with probability 0
+    ImpossibleEvidence
+}
+
+/**
+    Weighted model counting by DPLL style splitting
+
+    The weight of a model is the product of the weights of its literals.
+    Because the weights of x and !x always add up to 1, a variable that
+    no longer appears in any fact contributes a factor of 1 and can be ignored.
+    This means the count of a KB is the probability that a random assignment satisfies it.
+    Auxiliary variables weigh 1 both ways instead, which only works because each one
+    has exactly one value in every model and so never ends up free.
+
+    Facts that share no variables are counted separately and multiplied together.
+*/
+pub(super) struct ModelCounter<'a>{
+    weights: &'a VariableWeights
+}
+impl<'a> ModelCounter<'a>{
+    pub(super) fn new(weights: &'a VariableWeights)->Self{
+        Self{weights}
+    }
+    pub(super) fn count(&self, kb: &KnowledgeBase)->f64{
+        self.count_recursive(kb.facts().clone())
+    }
+    /// P(query | kb)
+    pub(super) fn conditional_probability(&self, kb: &KnowledgeBase, query: &KnowledgeBase)->Result<f64, Undefined>{
+        let kb_count = self.count(kb);
+        if kb_count == 0.0 {
+            //with every variable a coin flip each model weighs something
+            let satisfiable = ModelCounter::new(&VariableWeights::new()).count(kb) > 0.0;
+            return Err(if satisfiable {Undefined::ImpossibleEvidence} else {Undefined::Contradiction});
+        }
+        let mut facts = kb.facts().clone();
+        facts.extend(query.facts().iter().cloned());
+        Ok(self.count_recursive(facts) / kb_count)
+    }
+
+    fn count_recursive(&self, mut facts: Vec<KnowledgeBaseFact>)->f64{
+        let mut weight = 1.0;
+
+        //unit propagation
+        loop {
+            if facts.iter().any(|fact|fact.contradiction()) {
+                return 0.0;
+            }
+            let Some(unit) = facts.iter().find_map(|fact|fact.is_unit()).cloned() else {break};
+            weight *= self.weights.literal_weight(&unit);
+            facts = KnowledgeBaseFact::assign(facts, &unit);
+        }
+
+        if facts.is_empty() {
+            return weight;
+        }
+
+        let components = Self::components(facts);
+        if components.len() > 1 {
+            return components
+                .into_iter()
+                .fold(weight, |weight, component|weight * self.count_recursive(component));
+        }
+        let Some(facts) = components.into_iter().next() else {unreachable!()};
+
+        let Some(var) = Self::most_frequent_variable(&facts) else {unreachable!()};
+        let positive = KnowledgeBaseLiteral::new(false, var);
+        let negative = positive.negated();
+
+        let positive_count = self.weights.literal_weight(&positive) * self.count_recursive(KnowledgeBaseFact::assign(facts.clone(), &positive));
+        let negative_count = self.weights.literal_weight(&negative) * self.count_recursive(KnowledgeBaseFact::assign(facts, &negative));
+
+        weight * (positive_count + negative_count)
+    }
+
+    /// Splits facts into groups that share no variables
+    fn components(facts: Vec<KnowledgeBaseFact>)->Vec<Vec<KnowledgeBaseFact>>{
+        let mut components: Vec<(HashSet<Variable>, Vec<KnowledgeBaseFact>)> = Vec::new();
+
+        for fact in facts {
+            let vars: HashSet<Variable> = fact.literals().iter().map(|l|*l.var()).collect();
+
+            let mut merged = (vars, vec![fact]);
+            let mut i = 0;
+            while i < components.len() {
+                if components[i].0.is_disjoint(&merged.0) {
+                    i += 1;
+                    continue;
+                }
+                let (other_vars, other_facts) = components.swap_remove(i);
+                merged.0.extend(other_vars);
+                merged.1.extend(other_facts);
+            }
+            components.push(merged);
+        }
+
+        components.into_iter().map(|(_, facts)|facts).collect()
+    }
+
+    fn most_frequent_variable(facts: &[KnowledgeBaseFact])->Option<Variable>{
+        let mut occurrences: HashMap<&Variable, usize> = HashMap::new();
+        for literal in facts.iter().flat_map(|fact|fact.literals().iter()) {
+            *occurrences.entry(literal.var()).or_default() += 1;
+        }
+        occurrences
+            .into_iter()
+            .max_by_key(|(_, count)|*count)
+            .map(|(var, _)|*var)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::test_helpers::{models, random_facts, Random};
+
+    const VARIABLES: u32 = 6;
+
+    /// The weight of every model over the first VARIABLES variables added up
+    fn brute_force(facts: &[KnowledgeBaseFact], weights: &VariableWeights)->f64{
+        models(facts, VARIABLES).into_iter().map(|assignment|{
+            (0..VARIABLES).map(|var|{
+                let p = weights.probability(&Variable::new(var));
+                if assignment >> var & 1 == 1 {p} else {1.0 - p}
+            }).product::<f64>()
+        }).sum()
+    }
+    fn count(facts: &[KnowledgeBaseFact], weights: &VariableWeights)->f64{
+        ModelCounter::new(weights).count(&KnowledgeBase::from_facts(facts.to_vec()))
+    }
+    fn fact(literals: &[(bool, u32)])->KnowledgeBaseFact{
+        KnowledgeBaseFact::new(literals.iter().map(|(not, var)|KnowledgeBaseLiteral::new(*not, Variable::new(*var))).collect())
+    }
+
+    #[test]
+    fn empty_and_contradiction(){
+        let weights = VariableWeights::new();
+        assert_eq!(count(&[], &weights), 1.0);
+        assert_eq!(count(&[fact(&[])], &weights), 0.0);
+        assert_eq!(count(&[fact(&[(false, 0)]), fact(&[(true, 0)])], &weights), 0.0);
+        assert_eq!(ModelCounter::new(&weights).conditional_probability(&KnowledgeBase::from_facts(vec![fact(&[])]), &KnowledgeBase::tautology()), Err(Undefined::Contradiction));
+    }
+
+    /// Unmentioned variables count both ways, so the models over all of them are the count times 2^VARIABLES
+    #[test]
+    fn free_variables(){
+        let weights = VariableWeights::new();
+        let facts = [fact(&[(false, 0), (false, 1)])];
+        assert_eq!(count(&facts, &weights), 0.75);
+        assert_eq!(count(&facts, &weights) * (1 << VARIABLES) as f64, models(&facts, VARIABLES).len() as f64);
+        assert_eq!(models(&facts, VARIABLES).len(), 3 << (VARIABLES - 2));
+    }
+
+    /// a | b and c | d share nothing so they are counted apart and multiplied
+    #[test]
+    fn disjoint_components(){
+        let mut weights = VariableWeights::new();
+        weights.set_probability(Variable::new(0), 0.2);
+        weights.set_probability(Variable::new(3), 0.9);
+        let facts = [fact(&[(false, 0), (false, 1)]), fact(&[(false, 2), (false, 3)])];
+        let expected = (1.0 - 0.8 * 0.5) * (1.0 - 0.5 * 0.1);
+        assert!((count(&facts, &weights) - expected).abs() < 1e-12);
+        assert_eq!(ModelCounter::components(facts.to_vec()).len(), 2);
+    }
+
+    #[test]
+    fn matches_brute_force_with_weights(){
+        let mut random = Random(0xD1B54A32D192ED03);
+        for _ in 0..300 {
+            let mut weights = VariableWeights::new();
+            for var in 0..VARIABLES {
+                if random.below(3) != 0 {
+                    weights.set_probability(Variable::new(var), random.below(11) as f64 / 10.0);
+                }
+            }
+            let count_of_facts = random.below(8);
+            let facts = random_facts(&mut random, count_of_facts, 0..VARIABLES);
+            let expected = brute_force(&facts, &weights);
+            assert!((count(&facts, &weights) - expected).abs() < 1e-9, "{} against {}", count(&facts, &weights), expected);
+
+            let query = random_facts(&mut random, 1, 0..VARIABLES);
+            let both: Vec<KnowledgeBaseFact> = facts.iter().chain(query.iter()).cloned().collect();
+            let conditional = ModelCounter::new(&weights).conditional_probability(&KnowledgeBase::from_facts(facts.clone()), &KnowledgeBase::from_facts(query));
+            match conditional {
+                Err(_) => assert_eq!(expected, 0.0),
+                Ok(p) => assert!((p - brute_force(&both, &weights) / expected).abs() < 1e-9),
+            }
+        }
+    }
+
+    /// A probability of 0 or 1 can leave every model weighing nothing without being a contradiction
+    #[test]
+    fn evidence_with_probability_0(){
+        let mut weights = VariableWeights::new();
+        weights.set_probability(Variable::new(0), 0.0);
+        weights.set_probability(Variable::new(1), 1.0);
+        let counter = ModelCounter::new(&weights);
+        let probability = |facts: Vec<KnowledgeBaseFact>, query: Vec<KnowledgeBaseFact>|counter.conditional_probability(&KnowledgeBase::from_facts(facts), &KnowledgeBase::from_facts(query));
+
+        assert_eq!(probability(vec![fact(&[(false, 0)])], vec![]), Err(Undefined::ImpossibleEvidence));
+        assert_eq!(probability(vec![fact(&[(true, 1)]), fact(&[(false, 2)])], vec![]), Err(Undefined::ImpossibleEvidence));
+        assert_eq!(probability(vec![fact(&[(false, 0)]), fact(&[(true, 0)])], vec![]), Err(Undefined::Contradiction));
+        assert_eq!(probability(vec![fact(&[(false, 0), (false, 2)])], vec![fact(&[(false, 2)])]), Ok(1.0));
+        assert_eq!(probability(vec![fact(&[(false, 1), (false, 2)])], vec![fact(&[(false, 2)])]), Ok(0.5));
+    }
+}
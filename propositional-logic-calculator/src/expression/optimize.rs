@@ -0,0 +1,157 @@
+use super::{cardinality::totalizer, expression::{Expression, ExpressionNode}, knowledge_base::{KnowledgeBaseFact, KnowledgeBaseLiteral}, parser::ExpressionParseError, solver::{Model, Solver}, variable::Variable, KnowledgeBase, Workspace};
+
+/**
+    The model of kb with the fewest of literals true, and how many that is
+
+    Solves once, then keeps adding a bound of one fewer than the last model had
+    until there is no model, which proves the last one was the best.
+    The bound is a totalizer so tightening it is one more fact on the same solver.
+    Variables after every one in kb and literals are used for counting and left out of the model.
+*/
+pub(super) fn minimize(kb: &KnowledgeBase, literals: &[KnowledgeBaseLiteral])->Option<(Model, usize)>{
+    let first_fresh = kb.facts()
+        .iter()
+        .flat_map(|fact|fact.literals())
+        .chain(literals)
+        .map(|literal|literal.var().index() as u32 + 1)
+        .max()
+        .unwrap_or(0);
+    let mut solver = Solver::new(kb);
+    let mut next_fresh = first_fresh;
+    let mut fresh = ||{
+        next_fresh += 1;
+        Variable::new(next_fresh - 1)
+    };
+    let mut facts = Vec::new();
+    let outputs = totalizer(literals, &mut fresh, &mut facts);
+    for fact in facts.iter() {
+        solver.add_fact(fact);
+    }
+    let mut model = solver.solve()?;
+
+    let count = |model: &Model|literals.iter().filter(|literal|model.value(literal.var()) == Some(!literal.not())).count();
+    loop {
+        let best = count(&model);
+        if best == 0 {
+            break;
+        }
+        solver.add_fact(&KnowledgeBaseFact::new(vec![outputs[best - 1].negated()]));
+        match solver.solve() {
+            Some(better) => model = better,
+            None => break,
+        }
+    }
+
+    //one counted literal gets no totalizer facts, if the solver never saw it it can leave it false
+    for literal in literals {
+        if model.value(literal.var()).is_none() {
+            model.set(*literal.var(), literal.not());
+        }
+    }
+    let best = count(&model);
+    let mut named = Model::default();
+    for var in (0..first_fresh).map(Variable::new) {
+        if let Some(value) = model.value(&var) {
+            named.set(var, value);
+        }
+    }
+    Some((named, best))
+}
+
+impl Workspace{
+    /// The model of every hard expression with the fewest of literals true, and how many that is
+    /// None if the expressions contradict each other
+    pub fn minimize_count(&self, literals: &[KnowledgeBaseLiteral])->Option<(Model, usize)>{
+        minimize(&self.expression_set.knowledge_base(), literals).map(|(model, fewest)|(self.named_model(&model), fewest))
+    }
+    /// The most true is the fewest false
+    pub fn maximize_count(&self, literals: &[KnowledgeBaseLiteral])->Option<(Model, usize)>{
+        let negated: Vec<KnowledgeBaseLiteral> = literals.iter().map(|literal|literal.negated()).collect();
+        self.minimize_count(&negated).map(|(model, fewest)|(model, literals.len() - fewest))
+    }
+    /// minimize count(a, b, !c) or maximize count(a, b, !c)
+    pub(super) fn parse_objective(&mut self, maximize: bool, input: &str)->Result<String, ExpressionParseError>{
+        let Some(inner) = input.trim().strip_prefix("count(").and_then(|inner|inner.trim_end().strip_suffix(')')) else {
+            return Err(ExpressionParseError::General);
+        };
+        let mut literals = Vec::new();
+        for item in inner.split(',') {
+            let literal = match Expression::parse(item, &mut self.variable_names)?.node() {
+                ExpressionNode::Variable(var) => KnowledgeBaseLiteral::new(false, *var),
+                ExpressionNode::Not(a) => match a.as_ref() {
+                    ExpressionNode::Variable(var) => KnowledgeBaseLiteral::new(true, *var),
+                    _ => return Err(ExpressionParseError::General),
+                },
+                _ => return Err(ExpressionParseError::General),
+            };
+            literals.push(literal);
+        }
+
+        let counted: Vec<String> = literals.iter().map(|literal|self.variable_names.display_literal(literal)).collect();
+        let result = if maximize {self.maximize_count(&literals)} else {self.minimize_count(&literals)};
+        let Some((model, best)) = result else {
+            return Ok("Contradiction, the knowledge base has no models\n\n".to_string());
+        };
+        let (most, than, bound) = if maximize {("Most", "more", literals.len())} else {("Fewest", "fewer", 0)};
+        let mut out = format!("{} true of {}: {}\n", most, counted.join(", "), best);
+        //the solver showed this when the last bound had no model
+        if best != bound {
+            out.push_str(&format!("No model has {} than {}\n", than, best));
+        }
+        out.push_str(&format!("{}\n", model.display(&self.variable_names)));
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::test_helpers::{literal_holds, model_holds, models, random_facts, random_literal, Random};
+
+    /// Checks the fewest against trying every assignment
+    #[test]
+    fn matches_brute_force(){
+        let mut random = Random(0x2545F4914F6CDD1D);
+        let variables = 7;
+        for _ in 0..300 {
+            let count = random.below(10);
+            let facts = random_facts(&mut random, count, 0..variables);
+            let counted: Vec<KnowledgeBaseLiteral> = (0..1 + random.below(6)).map(|_|random_literal(&mut random, 0..variables)).collect();
+
+            let best = models(&facts, variables)
+                .into_iter()
+                .map(|assignment|counted.iter().filter(|literal|literal_holds(literal, assignment)).count())
+                .min();
+
+            let found = minimize(&KnowledgeBase::from_facts(facts.clone()), &counted);
+            assert_eq!(found.as_ref().map(|(_, fewest)|*fewest), best);
+            if let Some((model, fewest)) = found {
+                assert!(model_holds(&facts, &model));
+                assert_eq!(counted.iter().filter(|literal|model.value(literal.var()) == Some(!literal.not())).count(), fewest);
+            }
+        }
+    }
+
+    /// A counted variable nothing else mentions can't be mistaken for a counting variable
+    #[test]
+    fn counts_variables_outside_the_facts(){
+        let facts = vec![KnowledgeBaseFact::new(vec![KnowledgeBaseLiteral::new(false, Variable::new(0))])];
+        let counted = [KnowledgeBaseLiteral::new(true, Variable::new(0)), KnowledgeBaseLiteral::new(false, Variable::new(5)), KnowledgeBaseLiteral::new(true, Variable::new(9))];
+        let (model, fewest) = minimize(&KnowledgeBase::from_facts(facts), &counted).unwrap();
+        assert_eq!(fewest, 0);
+        assert_eq!(model.value(&Variable::new(5)), Some(false));
+        assert_eq!(model.value(&Variable::new(9)), Some(true));
+    }
+
+    /// With one counted literal the totalizer adds no facts, the model still says what it is
+    #[test]
+    fn one_counted_literal(){
+        let facts = vec![KnowledgeBaseFact::new(vec![KnowledgeBaseLiteral::new(false, Variable::new(0))])];
+        for not in [false, true] {
+            let counted = [KnowledgeBaseLiteral::new(not, Variable::new(3))];
+            let (model, fewest) = minimize(&KnowledgeBase::from_facts(facts.clone()), &counted).unwrap();
+            assert_eq!(fewest, 0);
+            assert_eq!(model.value(&Variable::new(3)), Some(not));
+        }
+    }
+}
@@ -4,7 +4,7 @@ use super::{expression::ExpressionNode, variable::Variable};
 
 
 #[derive(Debug, Clone, PartialEq)]
-enum ExpressionParserToken {
+pub(super) enum ExpressionParserToken {
     Or,
     And,
     Xor,
@@ -14,11 +14,24 @@ enum ExpressionParserToken {
     ImpliesRight,
     ImpliesLeft,
     Biconditional,
-    Variable(String),
+    Nand,
+    Nor,
+    Constant(bool),
+    /// A name and its indices, q[i][j] is q with i and j
+    Variable(String, Vec<String>),
+    Colon,
+    /// forall when all is true, exists when it isn't
+    Quantifier{all: bool, index: String, from: String, to: String},
 }
 
+/// Binds tighter than every binary operator
+pub(super) const NOT_PRECEDENCE: u8 = 6;
+/// The most copies of their bodies every forall and exists in an expression can make together,
+/// so nested ones count the product of their ranges
+const MAX_QUANTIFIER_RANGE: i64 = 10_000;
+
 #[derive(PartialEq, Debug)]
-enum Assoc {
+pub(super) enum Assoc {
     Left,
     Right,
 }
@@ -33,25 +46,27 @@ impl ExpressionParserToken {
                 | Self::ImpliesRight
                 | Self::ImpliesLeft
                 | Self::Biconditional
+                | Self::Nand
+                | Self::Nor
         )
     }
 
-    fn precedence(&self) -> Result<u8, ExpressionParseError> {
+    pub(super) fn precedence(&self) -> Result<u8, ExpressionParseError> {
         match self {
             Self::Biconditional => Ok(1),
             Self::ImpliesRight | Self::ImpliesLeft => Ok(2),
-            Self::Or => Ok(3),
+            Self::Or | Self::Nor => Ok(3),
             Self::Xor => Ok(4),
-            Self::And => Ok(5),
+            Self::And | Self::Nand => Ok(5),
             _ => Err(ExpressionParseError::General), //panic!("Not a binary operator"),
         }
     }
 
-    fn associativity(&self) -> Result<Assoc, ExpressionParseError> {
+    pub(super) fn associativity(&self) -> Result<Assoc, ExpressionParseError> {
         match self {
             Self::ImpliesRight | Self::ImpliesLeft => Ok(Assoc::Right),
             Self::Biconditional => Ok(Assoc::Left),
-            Self::Or | Self::Xor | Self::And => Ok(Assoc::Left),
+            Self::Or | Self::Xor | Self::And | Self::Nand | Self::Nor => Ok(Assoc::Left),
             _ => Err(ExpressionParseError::General), //panic!("Not a binary operator"),
         }
     }
@@ -59,44 +74,119 @@ impl ExpressionParserToken {
 
 /// For parsing
 /// Key characters are "<>^!|& _()"
-/// "_" and " " are ignored
+/// "!&" is nand and "!|" is nor
+/// "∧∨¬⊕→←↔↑↓" can be used instead of "&|!^><<>!&!|"
+/// "1" and "0" are true and false, so are "⊤" and "⊥"
+/// "_" is ignored and spaces only separate words, "anna bob" is annabob
+/// q[1][3] is a variable with indices, "forall i in 1..8: q[i]" is q[1] & ... & q[8] and exists is the same with |
+/// Ranges include both ends and an index can add and subtract, q[i+1][j-1]
 pub(super) struct ExpressionParser<'a> {
     variable_names: &'a mut HashMap<String, Variable>,
     tokens: Vec<ExpressionParserToken>,
     current: usize,
+    //the value of every quantified index, innermost last
+    indices: Vec<(String, i64)>,
+    //parsing the body of a quantifier over nothing, so no variables are made
+    skipping: bool,
+    //how many times a quantifier body has been parsed so far, nested ones included
+    expansions: i64,
 }
 #[derive(Debug)]
 pub enum ExpressionParseError{
-    General
+    General,
+    InvalidProbability,
+    InvalidWeight,
+    UnknownLabel,
+    DuplicateLabel,
+    UnknownVariable(String),
+    UnboundIndex(String),
+    /// The index is too big to calculate
+    IndexOverflow(String),
+    RangeTooLarge,
+    NothingToUndo,
+    NothingToRedo,
+    /// Someone new in a knights and knaves puzzle after a count, which is only over the people before it
+    PersonAfterCount(String),
+    /// :save or :dot could not write the file at path
+    Write{path: String, error: std::io::Error},
+    /// :load could not read the workspace at path
+    Load{path: String, error: super::LoadError}
+}
+impl std::fmt::Display for ExpressionParseError{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>)->std::fmt::Result{
+        match self {
+            Self::General => write!(f, "could not parse the input"),
+            Self::InvalidProbability => write!(f, "a probability has to be a number from 0 to 1 for a single variable"),
+            Self::InvalidWeight => write!(f, "a weight has to be a whole number above 0"),
+            Self::UnknownLabel => write!(f, "no expression has that label"),
+            Self::DuplicateLabel => write!(f, "another expression already has that label"),
+            Self::UnknownVariable(name) => write!(f, "no variable is called {}", name),
+            Self::UnboundIndex(index) => write!(f, "{} is not a number or the index of a forall or exists around it", index),
+            Self::IndexOverflow(index) => write!(f, "{} is too big to be an index", index),
+            Self::RangeTooLarge => write!(f, "the foralls and exists in an expression can go over at most {} values together", MAX_QUANTIFIER_RANGE),
+            Self::NothingToUndo => write!(f, "nothing to undo"),
+            Self::NothingToRedo => write!(f, "nothing to redo"),
+            Self::PersonAfterCount(name) => write!(f, "{} can't join the puzzle after a count, declare everyone first", name),
+            Self::Write{path, error} => write!(f, "could not write {}: {}", path, error),
+            Self::Load{path, error} => write!(f, "could not load {}: {}", path, error),
+        }
+    }
+}
+impl std::error::Error for ExpressionParseError{
+    fn source(&self)->Option<&(dyn std::error::Error + 'static)>{
+        match self {
+            Self::Write{error, ..} => Some(error),
+            Self::Load{error, ..} => Some(error),
+            _ => None,
+        }
+    }
 }
 
 impl<'a> ExpressionParser<'a> {
     pub fn parse_string(string: &str, variable_names: &mut HashMap<String, Variable>) -> Result<Box<ExpressionNode>, ExpressionParseError> {
         let filtered: String = string
             .chars()
-            .filter(|c| !['_', ' ', '\n'].contains(c))
+            .filter(|c| *c != '_')
             .collect();
-        let tokens = ExpressionParser::tokenize(&filtered);
+        let tokens = ExpressionParser::tokenize(&filtered)?;
         let mut parser = ExpressionParser {
             variable_names,
             tokens,
             current: 0,
+            indices: Vec::new(),
+            skipping: false,
+            expansions: 0,
         };
         let expr = parser.parse_expression(0)?;
+        if parser.current != parser.tokens.len() {
+            return Err(ExpressionParseError::General);
+        }
         Ok(expr)
     }
 
-    fn tokenize(s: &str) -> Vec<ExpressionParserToken> {
+    fn is_name_char(c: char) -> bool {
+        !c.is_whitespace()
+            && !matches!(
+                c,
+                '<' | '>' | '^' | '!' | '|' | '&' | '(' | ')' | '[' | ']' | ':'
+                    | '→' | '←' | '↔' | '⊕' | '¬' | '∨' | '∧' | '↑' | '↓' | '⊤' | '⊥'
+            )
+    }
+
+    /// Whitespace only separates words, "anna bob" is still the variable annabob
+    fn tokenize(s: &str) -> Result<Vec<ExpressionParserToken>, ExpressionParseError> {
         let chars: Vec<char> = s.chars().collect();
+        //the next character that isn't whitespace, so "! &" is still nand
+        let next = |i: usize| chars[i..].iter().position(|c| !c.is_whitespace()).map(|offset| (i + offset, chars[i + offset]));
         let mut tokens = Vec::new();
         let mut i = 0;
-        while i < chars.len() {
-            let c = chars[i];
+        while let Some((at, c)) = next(i) {
+            i = at;
             match c {
                 '<' => {
-                    if i + 1 < chars.len() && chars[i + 1] == '>' {
+                    if let Some((at, '>')) = next(i + 1) {
                         tokens.push(ExpressionParserToken::Biconditional);
-                        i += 2;
+                        i = at + 1;
                     } else {
                         tokens.push(ExpressionParserToken::ImpliesLeft);
                         i += 1;
@@ -106,19 +196,55 @@ impl<'a> ExpressionParser<'a> {
                     tokens.push(ExpressionParserToken::ImpliesRight);
                     i += 1;
                 }
-                '^' => {
+                '→' => {
+                    tokens.push(ExpressionParserToken::ImpliesRight);
+                    i += 1;
+                }
+                '←' => {
+                    tokens.push(ExpressionParserToken::ImpliesLeft);
+                    i += 1;
+                }
+                '↔' => {
+                    tokens.push(ExpressionParserToken::Biconditional);
+                    i += 1;
+                }
+                '^' | '⊕' => {
                     tokens.push(ExpressionParserToken::Xor);
                     i += 1;
                 }
-                '!' => {
+                '!' if matches!(next(i + 1), Some((_, '&'))) => {
+                    tokens.push(ExpressionParserToken::Nand);
+                    i = next(i + 1).map_or(i, |(at, _)| at) + 1;
+                }
+                '!' if matches!(next(i + 1), Some((_, '|'))) => {
+                    tokens.push(ExpressionParserToken::Nor);
+                    i = next(i + 1).map_or(i, |(at, _)| at) + 1;
+                }
+                '⊤' => {
+                    tokens.push(ExpressionParserToken::Constant(true));
+                    i += 1;
+                }
+                '⊥' => {
+                    tokens.push(ExpressionParserToken::Constant(false));
+                    i += 1;
+                }
+                '↑' => {
+                    tokens.push(ExpressionParserToken::Nand);
+                    i += 1;
+                }
+                '↓' => {
+                    tokens.push(ExpressionParserToken::Nor);
+                    i += 1;
+                }
+                '!' | '¬' => {
                     tokens.push(ExpressionParserToken::Not);
                     i += 1;
                 }
-                '|' => {
+                '|' | '∨' => {
                     tokens.push(ExpressionParserToken::Or);
                     i += 1;
                 }
-                '&' => {
+                '&' | '∧' => {
                     tokens.push(ExpressionParserToken::And);
                     i += 1;
                 }
@@ -130,27 +256,125 @@ impl<'a> ExpressionParser<'a> {
                     tokens.push(ExpressionParserToken::CloseParenthesis);
                     i += 1;
                 }
+                ':' => {
+                    tokens.push(ExpressionParserToken::Colon);
+                    i += 1;
+                }
+                '[' | ']' => return Err(ExpressionParseError::General),
                 _ => {
                     let start = i;
-                    while i < chars.len()
-                        && !matches!(
-                            chars[i],
-                            '<' | '>' | '^' | '!' | '|' | '&' | '(' | ')'
-                        )
-                    {
+                    while i < chars.len() && Self::is_name_char(chars[i]) {
                         i += 1;
                     }
-                    let name: String = chars[start..i].iter().collect();
-                    if name.is_empty() {
-                        panic!("Unexpected character: {}", c);
+                    let name: String = chars[start..i].iter().collect::<String>().to_lowercase();
+                    //each [..] after a name is one index, spaces inside don't matter
+                    let mut indices = Vec::new();
+                    while let Some((open, '[')) = next(i) {
+                        let Some(close) = chars[open..].iter().position(|c| *c == ']') else {return Err(ExpressionParseError::General)};
+                        let index: String = chars[open + 1..open + close].iter().filter(|c| !c.is_whitespace()).collect();
+                        indices.push(index.to_lowercase());
+                        i = open + close + 1;
                     }
-                    tokens.push(ExpressionParserToken::Variable(name.to_lowercase()))
+                    tokens.push(ExpressionParserToken::Variable(name, indices));
+                }
+            }
+        }
+        Ok(Self::join_words(tokens))
+    }
+
+    /**
+        Finds quantifiers, forall i in 1..8: and exists i in 1..8:
+        then joins words next to each other back into one name
+        and turns "1" and "0" into constants
+    */
+    fn join_words(tokens: Vec<ExpressionParserToken>) -> Vec<ExpressionParserToken> {
+        let mut out: Vec<ExpressionParserToken> = Vec::new();
+        let mut i = 0;
+        while i < tokens.len() {
+            if let Some((quantifier, length)) = Self::quantifier(&tokens[i..]) {
+                out.push(quantifier);
+                i += length;
+                continue;
+            }
+            match (out.last_mut(), &tokens[i]) {
+                (Some(ExpressionParserToken::Variable(name, indices)), ExpressionParserToken::Variable(word, more)) if indices.is_empty() => {
+                    name.push_str(word);
+                    indices.extend(more.iter().cloned());
                 }
+                (_, token) => out.push(token.clone()),
             }
+            i += 1;
         }
-        tokens
+        out.into_iter()
+            .map(|token| match token {
+                ExpressionParserToken::Variable(name, indices) if name == "1" && indices.is_empty() => ExpressionParserToken::Constant(true),
+                ExpressionParserToken::Variable(name, indices) if name == "0" && indices.is_empty() => ExpressionParserToken::Constant(false),
+                token => token,
+            })
+            .collect()
     }
+    /// The quantifier at the start of tokens and how many tokens it was
+    fn quantifier(tokens: &[ExpressionParserToken]) -> Option<(ExpressionParserToken, usize)> {
+        let word = |i: usize| match tokens.get(i) {
+            Some(ExpressionParserToken::Variable(name, indices)) if indices.is_empty() => Some(name.as_str()),
+            _ => None,
+        };
+        let all = match word(0)? {
+            "forall" => true,
+            "exists" => false,
+            _ => return None,
+        };
+        let index = word(1)?;
+        if word(2)? != "in" {
+            return None;
+        }
+        //the range can be split by spaces, 1 .. 8
+        let colon = tokens.iter().position(|token| *token == ExpressionParserToken::Colon)?;
+        let range = (3..colon).map(word).collect::<Option<String>>()?;
+        let (from, to) = range.split_once("..")?;
+        Some((ExpressionParserToken::Quantifier{all, index: index.to_string(), from: from.to_string(), to: to.to_string()}, colon + 1))
+    }
+
+    /**
+        The value of an index like 3, i or i+1
+        Bound names are looked up innermost first
+        An error instead of wrapping around when it doesn't fit in an i64
+    */
+    fn evaluate(&self, index: &str) -> Result<i64, ExpressionParseError> {
+        //split before every sign that isn't the first character
+        let mut terms = Vec::new();
+        let mut start = 0;
+        for (i, c) in index.char_indices() {
+            if i > 0 && (c == '+' || c == '-') {
+                terms.push(&index[start..i]);
+                start = i;
+            }
+        }
+        terms.push(&index[start..]);
 
+        let mut value: i64 = 0;
+        for term in terms {
+            let (sign, term) = match term.strip_prefix('-') {
+                Some(term) => (-1, term),
+                None => (1, term.strip_prefix('+').unwrap_or(term)),
+            };
+            let term_value = if term.chars().all(|c| c.is_ascii_digit()) && !term.is_empty() {
+                term.parse::<i64>().map_err(|_| ExpressionParseError::IndexOverflow(index.to_string()))?
+            } else {
+                match self.indices.iter().rev().find(|(name, _)| name == term) {
+                    Some((_, bound)) => *bound,
+                    None => return Err(ExpressionParseError::UnboundIndex(term.to_string())),
+                }
+            };
+            value = term_value
+                .checked_mul(sign)
+                .and_then(|term_value| value.checked_add(term_value))
+                .ok_or_else(|| ExpressionParseError::IndexOverflow(index.to_string()))?;
+        }
+        Ok(value)
+    }
+
+    #[allow(clippy::while_let_loop)]
     fn parse_expression(&mut self, min_precedence: u8) -> Result<Box<ExpressionNode>, ExpressionParseError> {
         let mut left = self.parse_prefix()?;
         loop {
@@ -191,29 +415,76 @@ impl<'a> ExpressionParser<'a> {
     fn parse_prefix(&mut self) -> Result<Box<ExpressionNode>, ExpressionParseError> {
         let Some(token) = self.consume_token() else {return Err(ExpressionParseError::General)};
         match token {
-            ExpressionParserToken::Variable(name) => {
+            ExpressionParserToken::Variable(name, indices) => {
+                let mut name = name;
+                for index in indices {
+                    name.push_str(&format!("[{}]", self.evaluate(&index)?));
+                }
+                if self.skipping {
+                    return Ok(ExpressionNode::new_constant(true));
+                }
                 // Capture the length BEFORE the entry borrow
-                let current_len = self.variable_names.len() as u8;
-                let var = self
+                let current_len = self.variable_names.len() as u32;
+                let var = *self
                     .variable_names
                     .entry(name)
-                    .or_insert_with(|| Variable::new(current_len)) // Use captured value
-                    .clone();
+                    .or_insert_with(|| Variable::new(current_len));
                 Ok(ExpressionNode::new_variable(var))
             }
+            ExpressionParserToken::Quantifier{all, index, from, to} => self.parse_quantifier(all, index, &from, &to),
+            ExpressionParserToken::Constant(value) => Ok(ExpressionNode::new_constant(value)),
             ExpressionParserToken::OpenParenthesis => {
                 let expr = self.parse_expression(0);
                 self.expect(ExpressionParserToken::CloseParenthesis)?;
                 expr
             }
             ExpressionParserToken::Not => {
-                let expr = self.parse_expression(6)?; // Precedence of Not is 6
+                let expr = self.parse_expression(NOT_PRECEDENCE)?;
                 Ok(ExpressionNode::new_not(expr))
             }
             _ => Err(ExpressionParseError::General) //panic!("Unexpected token in prefix position: {:?}", token),
         }
     }
 
+    /// Parses the body once for every value, the body goes as far right as it can
+    fn parse_quantifier(&mut self, all: bool, index: String, from: &str, to: &str) -> Result<Box<ExpressionNode>, ExpressionParseError> {
+        let (from, to) = (self.evaluate(from)?, self.evaluate(to)?);
+        //an empty range is fine, it only has to not be too long
+        if to.checked_sub(from).is_none_or(|length| length >= MAX_QUANTIFIER_RANGE) {
+            return Err(ExpressionParseError::RangeTooLarge);
+        }
+        let body = self.current;
+        let mut out: Option<Box<ExpressionNode>> = None;
+        for value in from..=to {
+            self.expansions += 1;
+            if self.expansions > MAX_QUANTIFIER_RANGE {
+                return Err(ExpressionParseError::RangeTooLarge);
+            }
+            self.current = body;
+            self.indices.push((index.clone(), value));
+            let expr = self.parse_expression(0);
+            self.indices.pop();
+            let expr = expr?;
+            out = Some(match out {
+                None => expr,
+                Some(out) if all => ExpressionNode::new_and(out, expr),
+                Some(out) => ExpressionNode::new_or(out, expr),
+            });
+        }
+        if let Some(out) = out {
+            return Ok(out);
+        }
+        //nothing to quantify over, the body still has to be skipped
+        let skipping = self.skipping;
+        self.skipping = true;
+        self.indices.push((index, from));
+        let skipped = self.parse_expression(0);
+        self.indices.pop();
+        self.skipping = skipping;
+        skipped?;
+        Ok(ExpressionNode::new_constant(all))
+    }
+
     fn combine_binary(
         &self,
         token: &ExpressionParserToken,
@@ -227,6 +498,8 @@ impl<'a> ExpressionParser<'a> {
             ExpressionParserToken::ImpliesRight => Ok(ExpressionNode::new_implies(left, right)),
             ExpressionParserToken::ImpliesLeft => Ok(ExpressionNode::new_implies(right, left)),
             ExpressionParserToken::Biconditional => Ok(ExpressionNode::new_biconditional(left, right)),
+            ExpressionParserToken::Nand => Ok(ExpressionNode::new_nand(left, right)),
+            ExpressionParserToken::Nor => Ok(ExpressionNode::new_nor(left, right)),
             _ => Err(ExpressionParseError::General) //panic!("Unexpected binary operator: {:?}", token),
         }
     }
@@ -0,0 +1,470 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{knowledge_base::{KnowledgeBaseFact, KnowledgeBaseLiteral}, simplifier::Simplifier, solver::Model, variable::Variable, KnowledgeBase, VariableNames};
+
+/// Resolvents of a variable are only kept if there are no more of them than the facts they replace
+/// and the variable is in at most this many facts
+const MAX_ELIMINATION_OCCURRENCES: usize = 16;
+const MAX_ROUNDS: usize = 16;
+
+/// What the preprocessor did, in order
+/// Undone in reverse to turn a model of the smaller KB into a model of the original
+#[derive(Debug, Clone)]
+pub(super) enum PreprocessStep{
+    /// var was replaced everywhere by literal
+    Substituted{var: Variable, literal: KnowledgeBaseLiteral},
+    /// A fact containing witness was removed when its variable was eliminated
+    Eliminated{witness: KnowledgeBaseLiteral, fact: KnowledgeBaseFact},
+    /// A fact was removed because it was blocked on witness
+    Blocked{witness: KnowledgeBaseLiteral, fact: KnowledgeBaseFact},
+    /// literal was removed from a fact by self subsuming resolution
+    Strengthened{literal: KnowledgeBaseLiteral, fact: KnowledgeBaseFact}
+}
+
+/**
+    Shrinks a KB before solving while keeping it satisfiable exactly when the original is
+
+    Equivalent literal substitution
+    [!x, y], [!y, x] means x is y, so x is replaced by y everywhere
+
+    Self subsuming resolution
+    [x, y], [!x, y, z] becomes [x, y], [y, z]
+
+    Blocked clause elimination
+    A fact is blocked on x if resolving on x with every fact containing !x gives a tautology
+
+    Bounded variable elimination
+    Every fact with x or !x is replaced by all the resolvents on x
+    if that does not make the KB bigger
+
+    Substitution, elimination and blocked facts can leave the smaller KB with models
+    that make a removed fact false, so every step is recorded and reconstruct fixes the model up.
+*/
+pub(super) struct Preprocessor{
+    facts: Vec<KnowledgeBaseFact>,
+    variables: HashSet<Variable>,
+    steps: Vec<PreprocessStep>
+}
+impl Preprocessor{
+    pub(super) fn new(kb: &KnowledgeBase)->Self{
+        let facts: Vec<KnowledgeBaseFact> = kb.facts().clone();
+        let variables = facts
+            .iter()
+            .flat_map(|fact|fact.literals().iter().map(|literal|*literal.var()))
+            .collect();
+        Self{facts, variables, steps: Vec::new()}
+    }
+    pub(super) fn knowledge_base(&self)->KnowledgeBase{
+        KnowledgeBase::from_facts(self.facts.clone())
+    }
+    pub(super) fn run(&mut self){
+        for _ in 0..MAX_ROUNDS {
+            self.remove_tautologies_and_duplicates();
+            let mut changed = self.substitute_equivalent_literals();
+            changed |= self.self_subsuming_resolution();
+            changed |= self.eliminate_blocked_facts();
+            changed |= self.eliminate_variables();
+            if !changed || self.contradiction() {
+                break;
+            }
+        }
+    }
+    fn contradiction(&self)->bool{
+        self.facts.iter().any(|fact|fact.contradiction())
+    }
+
+    /// Turns a model of the preprocessed KB into a model of the original
+    pub(super) fn reconstruct(&self, model: &Model)->Model{
+        let mut model = model.clone();
+        for var in self.variables.iter() {
+            if model.value(var).is_none() {
+                model.set(*var, false);
+            }
+        }
+        for step in self.steps.iter().rev() {
+            match step {
+                PreprocessStep::Substituted{var, literal} => {
+                    let value = model.value(literal.var()).unwrap_or(false) != literal.not();
+                    model.set(*var, value);
+                },
+                PreprocessStep::Eliminated{witness, fact} | PreprocessStep::Blocked{witness, fact} => {
+                    let satisfied = fact.literals().iter().any(|literal|model.value(literal.var()) == Some(!literal.not()));
+                    if !satisfied {
+                        model.set(*witness.var(), !witness.not());
+                    }
+                },
+                PreprocessStep::Strengthened{..} => {},
+            }
+        }
+        model
+    }
+
+    fn remove_tautologies_and_duplicates(&mut self){
+        let mut seen = HashSet::new();
+        self.facts.retain(|fact|!fact.tautology() && seen.insert(fact.clone()));
+    }
+    /// The index of every fact each literal is in
+    fn occurrences(&self)->HashMap<KnowledgeBaseLiteral, Vec<usize>>{
+        let mut occurrences: HashMap<KnowledgeBaseLiteral, Vec<usize>> = HashMap::new();
+        for (i, fact) in self.facts.iter().enumerate() {
+            for literal in fact.literals() {
+                occurrences.entry(literal.clone()).or_default().push(i);
+            }
+        }
+        occurrences
+    }
+
+    /// Finds cycles of binary implications, every literal in a cycle is equivalent
+    fn substitute_equivalent_literals(&mut self)->bool{
+        let mut implications: HashMap<KnowledgeBaseLiteral, Vec<KnowledgeBaseLiteral>> = HashMap::new();
+        for fact in self.facts.iter() {
+            let literals: Vec<&KnowledgeBaseLiteral> = fact.literals().iter().collect();
+            if let [a, b] = literals[..] {
+                implications.entry(a.negated()).or_default().push(b.clone());
+                implications.entry(b.negated()).or_default().push(a.clone());
+            }
+        }
+
+        let mut substitutions: HashMap<Variable, KnowledgeBaseLiteral> = HashMap::new();
+        for component in StronglyConnected::components(&implications) {
+            let Some(representative) = component.iter().min_by_key(|literal|literal.var()).cloned() else {continue};
+            for literal in component {
+                if literal.var() == representative.var() {
+                    if literal != representative {
+                        self.facts.push(KnowledgeBaseFact::new(Vec::new()));
+                        return true;
+                    }
+                    continue;
+                }
+                if literal.not() {
+                    substitutions.insert(*literal.var(), representative.negated());
+                }else{
+                    substitutions.insert(*literal.var(), representative.clone());
+                }
+            }
+        }
+        if substitutions.is_empty() {
+            return false;
+        }
+
+        let mut vars: Vec<&Variable> = substitutions.keys().collect();
+        vars.sort();
+        for var in vars {
+            self.steps.push(PreprocessStep::Substituted{var: *var, literal: substitutions[var].clone()});
+        }
+
+        self.facts = self.facts
+            .drain(..)
+            .map(|fact|{
+                KnowledgeBaseFact::new(fact.literals()
+                    .iter()
+                    .map(|literal|match substitutions.get(literal.var()) {
+                        Some(substitute) if literal.not() => substitute.negated(),
+                        Some(substitute) => substitute.clone(),
+                        None => literal.clone(),
+                    })
+                    .collect())
+            })
+            .collect();
+        self.remove_tautologies_and_duplicates();
+        true
+    }
+
+    /// Only facts with the negation of one of a fact's literals can be strengthened by it
+    /// Literals only ever get removed so the occurrence lists can be stale but never miss a fact
+    fn self_subsuming_resolution(&mut self)->bool{
+        let occurrences = self.occurrences();
+        let mut changed = false;
+        for i in 0..self.facts.len() {
+            let negated: Vec<KnowledgeBaseLiteral> = self.facts[i].literals().iter().map(|literal|literal.negated()).collect();
+            for literal in negated.iter() {
+                for j in occurrences.get(literal).into_iter().flatten().copied() {
+                    if i == j || self.facts[i].len() > self.facts[j].len() || !self.facts[j].contains(literal) {
+                        continue;
+                    }
+                    let Some(literal) = Self::strengthens(&self.facts[i], &self.facts[j]) else {continue};
+                    self.steps.push(PreprocessStep::Strengthened{literal: literal.clone(), fact: self.facts[j].clone()});
+                    self.facts[j].filter_literal(&literal);
+                    changed = true;
+                }
+            }
+        }
+        if changed {
+            self.remove_subsumed();
+        }
+        changed
+    }
+    /// If a is [x, ...rest] and b contains !x and all of rest then !x can be removed from b
+    fn strengthens(a: &KnowledgeBaseFact, b: &KnowledgeBaseFact)->Option<KnowledgeBaseLiteral>{
+        let mut clashing = None;
+        for literal in a.literals() {
+            if b.contains(literal) {
+                continue;
+            }
+            let negated = literal.negated();
+            if clashing.is_none() && b.contains(&negated) {
+                clashing = Some(negated);
+            }else{
+                return None;
+            }
+        }
+        clashing
+    }
+    fn remove_subsumed(&mut self){
+        self.facts = Simplifier::remove_redundant(std::mem::take(&mut self.facts));
+    }
+
+    /// Only the facts with the negation of the witness have to be resolved with
+    fn eliminate_blocked_facts(&mut self)->bool{
+        let occurrences = self.occurrences();
+        let mut removed = vec![false; self.facts.len()];
+        for i in 0..self.facts.len() {
+            let blocking = self.facts[i]
+                .literals()
+                .iter()
+                .find(|literal|{
+                    occurrences
+                        .get(&literal.negated())
+                        .into_iter()
+                        .flatten()
+                        .filter(|j|!removed[**j])
+                        .all(|j|Self::resolve(&self.facts[i], &self.facts[*j], literal).is_none())
+                })
+                .cloned();
+
+            if let Some(witness) = blocking {
+                removed[i] = true;
+                self.steps.push(PreprocessStep::Blocked{witness, fact: self.facts[i].clone()});
+            }
+        }
+        let changed = removed.contains(&true);
+        let mut removed = removed.into_iter();
+        self.facts.retain(|_|!removed.next().unwrap_or(false));
+        changed
+    }
+
+    fn eliminate_variables(&mut self)->bool{
+        let mut vars: Vec<Variable> = self.facts
+            .iter()
+            .flat_map(|fact|fact.literals().iter().map(|literal|*literal.var()))
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        vars.sort();
+
+        let mut changed = false;
+        for var in vars {
+            if self.contradiction() {
+                break;
+            }
+            let positive = KnowledgeBaseLiteral::new(false, var);
+            let negative = positive.negated();
+
+            let (positive_facts, negative_facts): (Vec<&KnowledgeBaseFact>, Vec<&KnowledgeBaseFact>) = self.facts
+                .iter()
+                .filter(|fact|fact.contains(&positive) || fact.contains(&negative))
+                .partition(|fact|fact.contains(&positive));
+            if positive_facts.len() + negative_facts.len() > MAX_ELIMINATION_OCCURRENCES {
+                continue;
+            }
+
+            let mut resolvents: Vec<KnowledgeBaseFact> = Vec::new();
+            for a in positive_facts.iter() {
+                for b in negative_facts.iter() {
+                    if let Some(resolvent) = Self::resolve(a, b, &positive) {
+                        if !resolvents.contains(&resolvent) {
+                            resolvents.push(resolvent);
+                        }
+                    }
+                }
+            }
+            if resolvents.len() > positive_facts.len() + negative_facts.len() {
+                continue;
+            }
+
+            let (removed, kept): (Vec<KnowledgeBaseFact>, Vec<KnowledgeBaseFact>) = self.facts
+                .drain(..)
+                .partition(|fact|fact.contains(&positive) || fact.contains(&negative));
+            for fact in removed {
+                let witness = if fact.contains(&positive) {positive.clone()} else {negative.clone()};
+                self.steps.push(PreprocessStep::Eliminated{witness, fact});
+            }
+            self.facts = kept;
+            self.facts.extend(resolvents);
+            changed = true;
+        }
+        changed
+    }
+
+    /// a contains literal and b contains its negation
+    /// None if the resolvent is a tautology
+    fn resolve(a: &KnowledgeBaseFact, b: &KnowledgeBaseFact, literal: &KnowledgeBaseLiteral)->Option<KnowledgeBaseFact>{
+        let negated = literal.negated();
+        let literals: Vec<KnowledgeBaseLiteral> = a.literals()
+            .iter()
+            .filter(|l|*l != literal)
+            .chain(b.literals().iter().filter(|l|**l != negated))
+            .cloned()
+            .collect();
+        let resolvent = KnowledgeBaseFact::new(literals);
+        if resolvent.tautology() {None} else {Some(resolvent)}
+    }
+
+    pub(super) fn display(&self, vars: &VariableNames)->String{
+        let mut out = String::new();
+        for step in self.steps.iter() {
+            let line = match step {
+                PreprocessStep::Substituted{var, literal} => format!(
+                    "substituted {} with {}",
+                    vars.display_variable(var),
+                    vars.display_literal(literal)
+                ),
+                PreprocessStep::Eliminated{witness, fact} => format!(
+                    "eliminated {} removing {}",
+                    vars.display_variable(witness.var()),
+                    vars.display_fact(fact)
+                ),
+                PreprocessStep::Blocked{witness, fact} => format!(
+                    "removed {} blocked on {}",
+                    vars.display_fact(fact),
+                    vars.display_literal(witness)
+                ),
+                PreprocessStep::Strengthened{literal, fact} => format!(
+                    "removed {} from {}",
+                    vars.display_literal(literal),
+                    vars.display_fact(fact)
+                ),
+            };
+            out.push_str(&line);
+            out.push('\n');
+        }
+        out
+    }
+}
+
+/// Tarjan's algorithm over the binary implication graph
+struct StronglyConnected<'a>{
+    graph: &'a HashMap<KnowledgeBaseLiteral, Vec<KnowledgeBaseLiteral>>,
+    index: HashMap<&'a KnowledgeBaseLiteral, usize>,
+    low_link: HashMap<&'a KnowledgeBaseLiteral, usize>,
+    stack: Vec<&'a KnowledgeBaseLiteral>,
+    on_stack: HashSet<&'a KnowledgeBaseLiteral>,
+    components: Vec<Vec<KnowledgeBaseLiteral>>
+}
+impl<'a> StronglyConnected<'a>{
+    /// Only components with more than one literal
+    fn components(graph: &'a HashMap<KnowledgeBaseLiteral, Vec<KnowledgeBaseLiteral>>)->Vec<Vec<KnowledgeBaseLiteral>>{
+        let mut search = Self{
+            graph,
+            index: HashMap::new(),
+            low_link: HashMap::new(),
+            stack: Vec::new(),
+            on_stack: HashSet::new(),
+            components: Vec::new()
+        };
+        let mut roots: Vec<&KnowledgeBaseLiteral> = graph.keys().collect();
+        roots.sort_by_key(|literal|(literal.var(), literal.not()));
+        for literal in roots {
+            if !search.index.contains_key(literal) {
+                search.visit(literal);
+            }
+        }
+        search.components
+    }
+    /// Depth first with an explicit stack so a long implication chain can't overflow
+    fn visit(&mut self, root: &'a KnowledgeBaseLiteral){
+        let graph = self.graph;
+        //each frame is a literal and how many of its successors have been looked at
+        let mut frames: Vec<(&'a KnowledgeBaseLiteral, usize)> = vec![(root, 0)];
+        self.open(root);
+
+        while let Some(&(literal, child)) = frames.last() {
+            let successors = graph.get(literal).map(|next|next.as_slice()).unwrap_or(&[]);
+            if let Some(next) = successors.get(child) {
+                if let Some(frame) = frames.last_mut() {
+                    frame.1 += 1;
+                }
+                if !self.index.contains_key(next) {
+                    self.open(next);
+                    frames.push((next, 0));
+                }else if self.on_stack.contains(next) {
+                    let low = self.low_link[literal].min(self.index[next]);
+                    self.low_link.insert(literal, low);
+                }
+                continue;
+            }
+
+            frames.pop();
+            if let Some(&(parent, _)) = frames.last() {
+                let low = self.low_link[parent].min(self.low_link[literal]);
+                self.low_link.insert(parent, low);
+            }
+            if self.low_link[literal] == self.index[literal] {
+                let mut component = Vec::new();
+                while let Some(top) = self.stack.pop() {
+                    self.on_stack.remove(top);
+                    component.push(top.clone());
+                    if top == literal {
+                        break;
+                    }
+                }
+                if component.len() > 1 {
+                    self.components.push(component);
+                }
+            }
+        }
+    }
+    fn open(&mut self, literal: &'a KnowledgeBaseLiteral){
+        let index = self.index.len();
+        self.index.insert(literal, index);
+        self.low_link.insert(literal, index);
+        self.stack.push(literal);
+        self.on_stack.insert(literal);
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::{solver::Solver, test_helpers::{model_holds, models, random_facts, Random}};
+
+    /// Preprocessing keeps satisfiability and every reconstructed model is a model of the original
+    #[test]
+    fn keeps_satisfiability_and_reconstructs_models(){
+        let mut random = Random(0xDA942042E4DD58B5);
+        let variables = 8;
+        for _ in 0..500 {
+            let count = random.below(24);
+            let facts = random_facts(&mut random, count, 0..variables);
+            let satisfiable = !models(&facts, variables).is_empty();
+
+            let mut preprocessor = Preprocessor::new(&KnowledgeBase::from_facts(facts.clone()));
+            preprocessor.run();
+            let model = Solver::new(&preprocessor.knowledge_base()).solve();
+            assert_eq!(model.is_some(), satisfiable);
+            if let Some(model) = model {
+                assert!(model_holds(&facts, &preprocessor.reconstruct(&model)));
+            }
+        }
+    }
+
+    /// A chain of equivalences long enough to overflow a recursive search
+    #[test]
+    fn long_equivalence_chain(){
+        let literal = |not: bool, var: u32|KnowledgeBaseLiteral::new(not, Variable::new(var));
+        let length = 200_000;
+        let facts: Vec<KnowledgeBaseFact> = (0..length)
+            .map(|var|KnowledgeBaseFact::new(vec![literal(true, var), literal(false, (var + 1) % length)]))
+            .collect();
+        let mut implications: HashMap<KnowledgeBaseLiteral, Vec<KnowledgeBaseLiteral>> = HashMap::new();
+        for fact in facts.iter() {
+            let [a, b] = &fact.literals()[..] else {unreachable!("every fact has two literals")};
+            implications.entry(a.negated()).or_default().push(b.clone());
+            implications.entry(b.negated()).or_default().push(a.clone());
+        }
+        let components = StronglyConnected::components(&implications);
+        let mut sizes: Vec<usize> = components.iter().map(|component|component.len()).collect();
+        sizes.sort();
+        assert_eq!(sizes, vec![length as usize, length as usize]);
+    }
+}
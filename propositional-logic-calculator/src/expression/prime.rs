@@ -0,0 +1,274 @@
+use std::collections::BTreeSet;
+
+use super::{knowledge_base::{KnowledgeBaseFact, KnowledgeBaseLiteral}, solver::Solver, variable::Variable, KnowledgeBase, Workspace};
+
+/// How many facts :implicates and :implicants keep before giving up, unless told otherwise
+pub(super) const DEFAULT_LIMIT: usize = 10000;
+
+/**
+    Every prime implicate of facts, the facts it entails that have no smaller fact it also entails
+
+    Tison's method: for every variable in turn, resolve each fact that has it
+    with each fact that has it negated, keeping only facts nothing else is a subset of.
+    Once every variable is done what is left is exactly the prime implicates.
+
+    There can be exponentially many, so None once more than limit facts are kept.
+*/
+pub(super) fn prime_implicates(facts: &[KnowledgeBaseFact], limit: usize)->Option<Vec<KnowledgeBaseFact>>{
+    let mut kept: Vec<KnowledgeBaseFact> = Vec::new();
+    for fact in facts.iter().filter(|fact|!fact.tautology()) {
+        add_minimal(&mut kept, fact.clone());
+    }
+    if kept.len() > limit {
+        return None;
+    }
+
+    let variables: BTreeSet<_> = kept.iter().flat_map(|fact|fact.literals()).map(|literal|*literal.var()).collect();
+    for var in variables {
+        let positive = KnowledgeBaseLiteral::new(false, var);
+        let negative = KnowledgeBaseLiteral::new(true, var);
+        //resolvents never have var so they can't make more pairs for it
+        let with: Vec<KnowledgeBaseFact> = kept.iter().filter(|fact|fact.contains(&positive)).cloned().collect();
+        let without: Vec<KnowledgeBaseFact> = kept.iter().filter(|fact|fact.contains(&negative)).cloned().collect();
+        for a in with.iter() {
+            for b in without.iter() {
+                let resolvent = KnowledgeBaseFact::new(
+                    a.literals().iter().chain(b.literals()).filter(|literal|*literal.var() != var).cloned().collect()
+                );
+                if resolvent.tautology() {
+                    continue;
+                }
+                add_minimal(&mut kept, resolvent);
+                if kept.len() > limit {
+                    return None;
+                }
+            }
+        }
+    }
+    Some(kept)
+}
+
+/**
+    Every prime implicant of facts, literals that all being true makes every fact true, with none of them unneeded
+
+    A set of literals makes a fact true when it has one of its literals,
+    so the implicants are the sets with a literal from every fact and never both x and !x,
+    and the prime ones are those with nothing left out.
+    A second solver picks them, literal x is variable 2x and !x is 2x + 1 in it.
+    Each set it finds is shrunk until nothing can be left out,
+    then a fact saying not all of that set is added so neither it nor a bigger one comes back.
+
+    Each one found is one more solve, so this stops at limit and gives None
+    without ever having more than limit + 1 of them.
+*/
+pub(super) fn prime_implicants(facts: &[KnowledgeBaseFact], limit: usize)->Option<Vec<Vec<KnowledgeBaseLiteral>>>{
+    let facts: Vec<&KnowledgeBaseFact> = facts.iter().filter(|fact|!fact.tautology()).collect();
+    let pick = |literal: &KnowledgeBaseLiteral|KnowledgeBaseLiteral::new(false, Variable::new(2 * literal.var().index() as u32 + literal.not() as u32));
+
+    let mut picking: Vec<KnowledgeBaseFact> = facts.iter().map(|fact|KnowledgeBaseFact::new(fact.literals().iter().map(pick).collect())).collect();
+    let variables: BTreeSet<Variable> = facts.iter().flat_map(|fact|fact.literals()).map(|literal|*literal.var()).collect();
+    for var in variables {
+        let [positive, negative] = [false, true].map(|not|pick(&KnowledgeBaseLiteral::new(not, var)).negated());
+        picking.push(KnowledgeBaseFact::new(vec![positive, negative]));
+    }
+    let mut solver = Solver::new(&KnowledgeBase::from_facts(picking));
+
+    let mut implicants = Vec::new();
+    while let Some(model) = solver.solve() {
+        if implicants.len() == limit {
+            return None;
+        }
+        let mut implicant: Vec<KnowledgeBaseLiteral> = facts
+            .iter()
+            .flat_map(|fact|fact.literals())
+            .filter(|literal|model.value(pick(literal).var()) == Some(true))
+            .cloned()
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        //taking out a literal only ever makes fewer facts true, so one pass is enough
+        let mut i = 0;
+        while i < implicant.len() {
+            let removed = implicant.remove(i);
+            if facts.iter().all(|fact|fact.literals().iter().any(|literal|implicant.contains(literal))) {
+                continue;
+            }
+            implicant.insert(i, removed);
+            i += 1;
+        }
+        solver.add_fact(&KnowledgeBaseFact::new(implicant.iter().map(|literal|pick(literal).negated()).collect()));
+        implicants.push(implicant);
+    }
+    implicants.sort();
+    Some(implicants)
+}
+
+/// Adds fact unless something kept is a subset of it, dropping what it is a subset of
+pub(super) fn add_minimal(kept: &mut Vec<KnowledgeBaseFact>, fact: KnowledgeBaseFact){
+    if kept.iter().any(|existing|existing.is_subset(&fact)) {
+        return;
+    }
+    kept.retain(|existing|!fact.is_subset(existing));
+    kept.push(fact);
+}
+
+impl KnowledgeBase{
+    /// The strongest facts this entails, None if there are more than limit of them
+    pub fn prime_implicates(&self, limit: usize)->Option<KnowledgeBase>{
+        prime_implicates(self.facts(), limit).map(KnowledgeBase::from_facts)
+    }
+}
+
+impl Workspace{
+    /// The prime implicates of every hard expression, None if there are more than limit
+    pub fn prime_implicates(&self, limit: usize)->Option<KnowledgeBase>{
+        self.named_knowledge_base().prime_implicates(limit)
+    }
+    /**
+        The prime implicants of every hard expression together,
+        each one is literals that all being true makes every expression true, with none of them unneeded.
+        None if there are more than limit.
+    */
+    pub fn prime_implicants(&self, limit: usize)->Option<Vec<Vec<KnowledgeBaseLiteral>>>{
+        prime_implicants(self.named_knowledge_base().facts(), limit)
+    }
+    pub fn display_prime_implicates(&self, limit: usize)->String{
+        match self.prime_implicates(limit) {
+            Some(kb) if kb.facts().is_empty() => "Nothing, every assignment is a model\n\n".to_string(),
+            Some(kb) => format!("{}\n", kb.display(&self.variable_names)),
+            None => format!("More than {} prime implicates, :implicates {} allows more\n\n", limit, limit * 10),
+        }
+    }
+    /// A line for each prime implicant with its literals anded
+    pub fn display_prime_implicants(&self, limit: usize)->String{
+        match self.prime_implicants(limit) {
+            Some(implicants) if implicants.is_empty() => "Nothing, the knowledge base has no models\n\n".to_string(),
+            Some(implicants) => {
+                let mut out = String::new();
+                for implicant in implicants {
+                    let literals: Vec<String> = implicant.iter().map(|literal|self.variable_names.display_literal(literal)).collect();
+                    if literals.is_empty() {
+                        out.push_str("1\n");
+                    }else{
+                        out.push_str(&format!("{}\n", literals.join(" & ")));
+                    }
+                }
+                out.push('\n');
+                out
+            },
+            None => format!("More than {} prime implicants, :implicants {} allows more\n\n", limit, limit * 10),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::{test_helpers::{holds, models, random_facts, Random}, variable::Variable};
+
+    /// Checks against every fact over the variables, prime when entailed and no smaller one is
+    #[test]
+    fn matches_brute_force(){
+        let mut random = Random(0x853C49E6748FEA9B);
+        let variables = 4u32;
+        //0 leaves a variable out, 1 is positive and 2 is negative
+        let every_fact: Vec<KnowledgeBaseFact> = (0..3u32.pow(variables)).map(|mut code|{
+            let mut literals = Vec::new();
+            for var in 0..variables {
+                if code % 3 != 0 {
+                    literals.push(KnowledgeBaseLiteral::new(code % 3 == 2, Variable::new(var)));
+                }
+                code /= 3;
+            }
+            KnowledgeBaseFact::new(literals)
+        }).collect();
+
+        for _ in 0..200 {
+            let count = random.below(7);
+            let facts = random_facts(&mut random, count, 0..variables);
+            let models = models(&facts, variables);
+            let entailed = |fact: &KnowledgeBaseFact|models.iter().all(|model|holds(std::slice::from_ref(fact), *model));
+
+            let expected: BTreeSet<Vec<KnowledgeBaseLiteral>> = every_fact
+                .iter()
+                .filter(|fact|entailed(fact) && !every_fact.iter().any(|smaller|smaller != *fact && smaller.is_subset(fact) && entailed(smaller)))
+                .map(|fact|fact.literals().clone())
+                .collect();
+            let found: BTreeSet<Vec<KnowledgeBaseLiteral>> = prime_implicates(&facts, usize::MAX)
+                .unwrap()
+                .iter()
+                .map(|fact|fact.literals().clone())
+                .collect();
+            assert_eq!(found, expected);
+        }
+    }
+
+    #[test]
+    fn gives_up_past_the_limit(){
+        let fact = |literals: &[(bool, u32)]|KnowledgeBaseFact::new(literals.iter().map(|(not, var)|KnowledgeBaseLiteral::new(*not, Variable::new(*var))).collect());
+        let facts = [fact(&[(false, 0), (false, 1)]), fact(&[(true, 0), (false, 2)])];
+        assert_eq!(prime_implicates(&facts, 3).map(|facts|facts.len()), Some(3));
+        assert!(prime_implicates(&facts, 2).is_none());
+    }
+
+    /// Checks against every set of literals, prime when it makes every fact true and no smaller one does
+    #[test]
+    fn implicants_match_brute_force(){
+        let mut random = Random(0x6C8E9CF570932BD5);
+        let variables = 4u32;
+        //0 leaves a variable out, 1 is positive and 2 is negative
+        let every_term: Vec<Vec<KnowledgeBaseLiteral>> = (0..3u32.pow(variables)).map(|mut code|{
+            let mut literals = Vec::new();
+            for var in 0..variables {
+                if code % 3 != 0 {
+                    literals.push(KnowledgeBaseLiteral::new(code % 3 == 2, Variable::new(var)));
+                }
+                code /= 3;
+            }
+            literals
+        }).collect();
+
+        for _ in 0..200 {
+            let count = random.below(7);
+            let facts = random_facts(&mut random, count, 0..variables);
+            let implies = |term: &Vec<KnowledgeBaseLiteral>|facts.iter().all(|fact|fact.tautology() || fact.literals().iter().any(|literal|term.contains(literal)));
+
+            let expected: Vec<Vec<KnowledgeBaseLiteral>> = every_term
+                .iter()
+                .filter(|term|implies(term) && !term.iter().any(|left_out|implies(&term.iter().filter(|literal|*literal != left_out).cloned().collect())))
+                .cloned()
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .collect();
+            assert_eq!(prime_implicants(&facts, usize::MAX), Some(expected));
+        }
+    }
+
+    #[test]
+    fn implicants_stop_at_the_limit(){
+        //(x0 | x1) & (x2 | x3) & ... has 2 to the power of how many facts
+        let facts: Vec<KnowledgeBaseFact> = (0..40).map(|i|KnowledgeBaseFact::new(vec![KnowledgeBaseLiteral::new(false, Variable::new(2 * i)), KnowledgeBaseLiteral::new(false, Variable::new(2 * i + 1))])).collect();
+        assert!(prime_implicants(&facts, 100).is_none());
+        assert_eq!(prime_implicants(&facts[..2], 4).map(|implicants|implicants.len()), Some(4));
+        assert!(prime_implicants(&facts[..2], 3).is_none());
+        assert_eq!(prime_implicants(&[], 1), Some(vec![vec![]]));
+        assert_eq!(prime_implicants(&[KnowledgeBaseFact::new(vec![])], 1), Some(vec![]));
+    }
+
+    /// A chain of xors gets fresh variables, they never show up and the rest is the same as distributing
+    #[test]
+    fn fresh_variables_are_left_out(){
+        let mut workspace = Workspace::new();
+        workspace.parse_line("a ^ b ^ c ^ d ^ e").unwrap();
+        let kb = workspace.expression_set.knowledge_base();
+        assert!(!workspace.fresh_variables(&kb).is_empty());
+
+        let named = workspace.variable_names.variables();
+        let implicates = workspace.prime_implicates(DEFAULT_LIMIT).unwrap();
+        assert_eq!(implicates.facts().len(), 16);
+        assert!(implicates.facts().iter().all(|fact|fact.literals().iter().all(|literal|named.contains(literal.var()))));
+        let implicants = workspace.prime_implicants(DEFAULT_LIMIT).unwrap();
+        assert_eq!(implicants.len(), 16);
+        assert!(implicants.iter().all(|implicant|implicant.len() == 5));
+    }
+}
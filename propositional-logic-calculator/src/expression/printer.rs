@@ -0,0 +1,193 @@
+use super::{expression::ExpressionNode, parser::{Assoc, ExpressionParserToken, NOT_PRECEDENCE}, VariableNames};
+
+/// How operators are written
+/// Ascii and Unicode can be parsed back in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(super) enum OperatorStyle{
+    #[default]
+    Ascii,
+    Unicode,
+    Latex,
+    Words
+}
+impl OperatorStyle{
+    pub(super) fn from_name(name: &str)->Option<Self>{
+        match name {
+            "ascii" => Some(Self::Ascii),
+            "unicode" => Some(Self::Unicode),
+            "latex" => Some(Self::Latex),
+            "words" => Some(Self::Words),
+            _ => None,
+        }
+    }
+    pub(super) fn name(&self)->&'static str{
+        match self {
+            Self::Ascii => "ascii",
+            Self::Unicode => "unicode",
+            Self::Latex => "latex",
+            Self::Words => "words",
+        }
+    }
+    pub(super) fn symbol(&self, token: &ExpressionParserToken)->&'static str{
+        match (self, token) {
+            (Self::Ascii, ExpressionParserToken::Constant(true)) => "1",
+            (Self::Ascii, ExpressionParserToken::Constant(false)) => "0",
+            (Self::Ascii, ExpressionParserToken::And) => "&",
+            (Self::Ascii, ExpressionParserToken::Or) => "|",
+            (Self::Ascii, ExpressionParserToken::Xor) => "^",
+            (Self::Ascii, ExpressionParserToken::Not) => "!",
+            (Self::Ascii, ExpressionParserToken::ImpliesRight) => ">",
+            (Self::Ascii, ExpressionParserToken::Biconditional) => "<>",
+            (Self::Ascii, ExpressionParserToken::Nand) => "!&",
+            (Self::Ascii, ExpressionParserToken::Nor) => "!|",
+
+            (Self::Unicode, ExpressionParserToken::Constant(true)) => "⊤",
+            (Self::Unicode, ExpressionParserToken::Constant(false)) => "⊥",
+            (Self::Unicode, ExpressionParserToken::And) => "∧",
+            (Self::Unicode, ExpressionParserToken::Or) => "∨",
+            (Self::Unicode, ExpressionParserToken::Xor) => "⊕",
+            (Self::Unicode, ExpressionParserToken::Not) => "¬",
+            (Self::Unicode, ExpressionParserToken::ImpliesRight) => "→",
+            (Self::Unicode, ExpressionParserToken::Biconditional) => "↔",
+            (Self::Unicode, ExpressionParserToken::Nand) => "↑",
+            (Self::Unicode, ExpressionParserToken::Nor) => "↓",
+
+            (Self::Latex, ExpressionParserToken::Constant(true)) => "\\top",
+            (Self::Latex, ExpressionParserToken::Constant(false)) => "\\bot",
+            (Self::Latex, ExpressionParserToken::And) => "\\land",
+            (Self::Latex, ExpressionParserToken::Or) => "\\lor",
+            (Self::Latex, ExpressionParserToken::Xor) => "\\oplus",
+            (Self::Latex, ExpressionParserToken::Not) => "\\lnot ",
+            (Self::Latex, ExpressionParserToken::ImpliesRight) => "\\rightarrow",
+            (Self::Latex, ExpressionParserToken::Biconditional) => "\\leftrightarrow",
+            (Self::Latex, ExpressionParserToken::Nand) => "\\uparrow",
+            (Self::Latex, ExpressionParserToken::Nor) => "\\downarrow",
+
+            (Self::Words, ExpressionParserToken::Constant(true)) => "true",
+            (Self::Words, ExpressionParserToken::Constant(false)) => "false",
+            (Self::Words, ExpressionParserToken::And) => "and",
+            (Self::Words, ExpressionParserToken::Or) => "or",
+            (Self::Words, ExpressionParserToken::Xor) => "xor",
+            (Self::Words, ExpressionParserToken::Not) => "not ",
+            (Self::Words, ExpressionParserToken::ImpliesRight) => "implies",
+            (Self::Words, ExpressionParserToken::Biconditional) => "iff",
+            (Self::Words, ExpressionParserToken::Nand) => "nand",
+            (Self::Words, ExpressionParserToken::Nor) => "nor",
+
+            _ => unreachable!("not an operator or constant"),
+        }
+    }
+}
+
+/**
+    Prints an expression with as few parentheses as the parser needs to read it back the same
+
+    Uses the parser's precedence and associativity
+    A child needs parentheses if it binds looser than its parent,
+    or binds the same but is on the side associativity doesn't group
+    a | (b | c) keeps its parentheses, (a | b) | c doesn't
+*/
+pub(super) struct ExpressionPrinter<'a>{
+    vars: &'a VariableNames,
+    style: OperatorStyle
+}
+impl<'a> ExpressionPrinter<'a>{
+    pub(super) fn new(vars: &'a VariableNames, style: OperatorStyle)->Self{
+        Self{vars, style}
+    }
+    pub(super) fn print(&self, node: &ExpressionNode)->String{
+        let mut out = String::new();
+        self.print_recursive(node, 0, &mut out);
+        out
+    }
+
+    fn print_recursive(&self, node: &ExpressionNode, min_precedence: u8, out: &mut String){
+        match node {
+            ExpressionNode::Variable(var) => out.push_str(&self.vars.display_variable(var)),
+            ExpressionNode::Constant(value) => out.push_str(self.style.symbol(&ExpressionParserToken::Constant(*value))),
+            ExpressionNode::Not(a) => {
+                let parenthesize = NOT_PRECEDENCE < min_precedence;
+                if parenthesize {out.push('(')}
+                out.push_str(self.style.symbol(&ExpressionParserToken::Not));
+                self.print_recursive(a, NOT_PRECEDENCE, out);
+                if parenthesize {out.push(')')}
+            },
+            ExpressionNode::And(a, b) => self.print_binary(ExpressionParserToken::And, a, b, min_precedence, out),
+            ExpressionNode::Or(a, b) => self.print_binary(ExpressionParserToken::Or, a, b, min_precedence, out),
+            ExpressionNode::Xor(a, b) => self.print_binary(ExpressionParserToken::Xor, a, b, min_precedence, out),
+            ExpressionNode::Implies(a, b) => self.print_binary(ExpressionParserToken::ImpliesRight, a, b, min_precedence, out),
+            ExpressionNode::Iff(a, b) => self.print_binary(ExpressionParserToken::Biconditional, a, b, min_precedence, out),
+            ExpressionNode::Nand(a, b) => self.print_binary(ExpressionParserToken::Nand, a, b, min_precedence, out),
+            ExpressionNode::Nor(a, b) => self.print_binary(ExpressionParserToken::Nor, a, b, min_precedence, out),
+        }
+    }
+
+    fn print_binary(&self, token: ExpressionParserToken, a: &ExpressionNode, b: &ExpressionNode, min_precedence: u8, out: &mut String){
+        let (Ok(precedence), Ok(associativity)) = (token.precedence(), token.associativity()) else {unreachable!()};
+        let (left_precedence, right_precedence) = match associativity {
+            Assoc::Left => (precedence, precedence + 1),
+            Assoc::Right => (precedence + 1, precedence),
+        };
+
+        let parenthesize = precedence < min_precedence;
+        if parenthesize {out.push('(')}
+        self.print_recursive(a, left_precedence, out);
+        out.push(' ');
+        out.push_str(self.style.symbol(&token));
+        out.push(' ');
+        self.print_recursive(b, right_precedence, out);
+        if parenthesize {out.push(')')}
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use std::collections::HashMap;
+
+    use super::super::{parser::ExpressionParser, test_helpers::{random_node, Random}, variable::Variable};
+    use super::*;
+
+    fn variable_names()->VariableNames{
+        let names: HashMap<String, Variable> = ["a", "b", "c", "d", "e"]
+            .iter()
+            .enumerate()
+            .map(|(i, name)|(name.to_string(), Variable::new(i as u32)))
+            .collect();
+        VariableNames{names}
+    }
+
+    #[test]
+    fn round_trip(){
+        let mut vars = variable_names();
+        let mut random = Random(0x9E3779B97F4A7C15);
+
+        for _ in 0..5000 {
+            let node = random_node(&mut random, 6);
+            for style in [OperatorStyle::Ascii, OperatorStyle::Unicode] {
+                let printed = ExpressionPrinter::new(&vars, style).print(&node);
+                let Ok(parsed) = ExpressionParser::parse_string(&printed, &mut vars.names) else {panic!("could not parse {}", printed)};
+                assert_eq!(parsed, node, "{}", printed);
+            }
+        }
+    }
+
+    #[test]
+    fn minimal_parentheses(){
+        let mut vars = variable_names();
+        for (input, expected) in [
+            ("(a | b) | c", "a | b | c"),
+            ("a | (b | c)", "a | (b | c)"),
+            ("(a & b) | c", "a & b | c"),
+            ("(a | b) & c", "(a | b) & c"),
+            ("!(a & b)", "!(a & b)"),
+            ("!!a", "!!a"),
+            ("a > b > c", "a > b > c"),
+            ("(a > b) > c", "(a > b) > c"),
+            ("a !& !b", "a !& !b"),
+            ("a < b", "b > a"),
+        ] {
+            let Ok(node) = ExpressionParser::parse_string(input, &mut vars.names) else {panic!("could not parse {}", input)};
+            assert_eq!(ExpressionPrinter::new(&vars, OperatorStyle::Ascii).print(&node), expected);
+        }
+    }
+}
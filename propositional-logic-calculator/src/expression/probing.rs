@@ -0,0 +1,218 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{knowledge_base::{KnowledgeBaseFact, KnowledgeBaseLiteral}, variable::Variable};
+
+/// How much work probing is allowed to do
+/// Every literal that is probed costs 1
+/// 0 turns probing off
+#[derive(Debug, Clone, Copy)]
+pub(super) struct ProbeBudget{
+    pub(super) probes: usize
+}
+impl Default for ProbeBudget{
+    fn default() -> Self {
+        Self{probes: 10_000}
+    }
+}
+
+enum ProbeResult{
+    Conflict,
+    /// Every literal that became true and whether a fact with more than 2 literals forced it
+    Implied(Vec<(KnowledgeBaseLiteral, bool)>),
+    OutOfBudget
+}
+
+/**
+    Failed literal probing and hyper binary resolution
+
+    Each literal is assumed and unit propagated
+    If that leads to a contradiction then the literal is failed and its negation must be true
+    [x, z], [!x, y], [!z, y] probing !y makes !x, !z then [x, z] is empty, so [y] is added
+
+    If x and !x both imply y then y must be true
+
+    If x implies y using a fact with more than 2 literals then [!x, y] is added (hyper binary resolution)
+    Binary implications are not added because they are already facts
+
+    Propagation only looks at the facts that have the negation of a literal that just became true,
+    found through occurrence lists built once for every probe.
+*/
+pub(super) struct Prober<'a>{
+    facts: &'a [KnowledgeBaseFact],
+    //the facts each literal is in
+    occurrences: HashMap<&'a KnowledgeBaseLiteral, Vec<usize>>,
+    remaining: usize
+}
+impl<'a> Prober<'a>{
+    pub(super) fn new(facts: &'a [KnowledgeBaseFact], budget: &ProbeBudget)->Self{
+        let mut occurrences: HashMap<&KnowledgeBaseLiteral, Vec<usize>> = HashMap::new();
+        for (index, fact) in facts.iter().enumerate() {
+            for literal in fact.literals() {
+                occurrences.entry(literal).or_default().push(index);
+            }
+        }
+        Self{facts, occurrences, remaining: budget.probes}
+    }
+    pub(super) fn remaining(&self)->usize{
+        self.remaining
+    }
+    /// New facts that are implied by the given ones
+    pub(super) fn derive(&mut self)->Vec<KnowledgeBaseFact>{
+        let mut variables: Vec<&Variable> = self.facts
+            .iter()
+            .flat_map(|fact|fact.literals().iter().map(|literal|literal.var()))
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        variables.sort();
+
+        let mut derived = Vec::new();
+        for var in variables {
+            let positive = KnowledgeBaseLiteral::new(false, *var);
+            let negative = positive.negated();
+
+            let positive_result = self.probe(&positive);
+            let negative_result = self.probe(&negative);
+
+            match (positive_result, negative_result) {
+                (ProbeResult::OutOfBudget, _) | (_, ProbeResult::OutOfBudget) => break,
+                (ProbeResult::Conflict, ProbeResult::Conflict) => {
+                    derived.push(KnowledgeBaseFact::new(Vec::new()));
+                    break;
+                },
+                (ProbeResult::Conflict, _) => derived.push(Self::unit(negative)),
+                (_, ProbeResult::Conflict) => derived.push(Self::unit(positive)),
+                (ProbeResult::Implied(positive_implied), ProbeResult::Implied(negative_implied)) => {
+                    let negative_set: HashSet<&KnowledgeBaseLiteral> = negative_implied.iter().map(|(literal, _)|literal).collect();
+                    for (literal, _) in positive_implied.iter() {
+                        if negative_set.contains(literal) {
+                            derived.push(Self::unit(literal.clone()));
+                        }
+                    }
+                    for (assumed, implied) in [(&positive, positive_implied.iter()), (&negative, negative_implied.iter())] {
+                        for (literal, hyper) in implied {
+                            if *hyper {
+                                derived.push(KnowledgeBaseFact::new(vec![assumed.negated(), literal.clone()]));
+                            }
+                        }
+                    }
+                },
+            }
+        }
+        derived
+    }
+
+    fn unit(literal: KnowledgeBaseLiteral)->KnowledgeBaseFact{
+        KnowledgeBaseFact::new(vec![literal])
+    }
+
+    fn probe(&mut self, assumed: &KnowledgeBaseLiteral)->ProbeResult{
+        if self.remaining == 0 {
+            return ProbeResult::OutOfBudget;
+        }
+        self.remaining -= 1;
+
+        let mut assignment: HashMap<&Variable, bool> = HashMap::new();
+        assignment.insert(assumed.var(), !assumed.not());
+        let mut implied = Vec::new();
+        let mut queue = vec![assumed.clone()];
+
+        while let Some(literal) = queue.pop() {
+            //only facts with the negation can have lost their last way to hold
+            let Some(watching) = self.occurrences.get(&literal.negated()) else {continue};
+            for fact in watching.iter().map(|index|&self.facts[*index]) {
+                let mut satisfied = false;
+                let mut unassigned = None;
+                let mut unassigned_count = 0;
+                for literal in fact.literals() {
+                    match assignment.get(literal.var()) {
+                        Some(value) if *value != literal.not() => {satisfied = true; break},
+                        Some(_) => {},
+                        None => {
+                            unassigned = Some(literal);
+                            unassigned_count += 1;
+                        },
+                    }
+                }
+                if satisfied {
+                    continue;
+                }
+                match (unassigned_count, unassigned) {
+                    (0, _) => return ProbeResult::Conflict,
+                    (1, Some(literal)) => {
+                        assignment.insert(literal.var(), !literal.not());
+                        implied.push((literal.clone(), fact.literals().len() > 2));
+                        queue.push(literal.clone());
+                    },
+                    _ => {}
+                }
+            }
+        }
+        ProbeResult::Implied(implied)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::test_helpers::{holds, models, random_facts, Random};
+
+    fn fact(literals: &[(bool, u32)])->KnowledgeBaseFact{
+        KnowledgeBaseFact::new(literals.iter().map(|(not, var)|KnowledgeBaseLiteral::new(*not, Variable::new(*var))).collect())
+    }
+    fn derive(facts: &[KnowledgeBaseFact], probes: usize)->Vec<KnowledgeBaseFact>{
+        Prober::new(facts, &ProbeBudget{probes}).derive()
+    }
+
+    /// [x, z], [!x, y], [!z, y], probing !y makes [x, z] empty so y has to be true
+    #[test]
+    fn failed_literal(){
+        let facts = [fact(&[(false, 0), (false, 2)]), fact(&[(true, 0), (false, 1)]), fact(&[(true, 2), (false, 1)])];
+        assert!(derive(&facts, 100).contains(&fact(&[(false, 1)])));
+    }
+
+    /// a and !a both make c true
+    #[test]
+    fn both_sides_imply(){
+        let facts = [fact(&[(true, 0), (false, 2)]), fact(&[(false, 0), (false, 1)]), fact(&[(true, 1), (false, 2)])];
+        assert!(derive(&facts, 100).contains(&fact(&[(false, 2)])));
+    }
+
+    /// a makes b true through a binary fact, then c through [!a, !b, c], so [!a, c] is added
+    #[test]
+    fn hyper_binary_resolution(){
+        let facts = [fact(&[(true, 0), (false, 1)]), fact(&[(true, 0), (true, 1), (false, 2)])];
+        let derived = derive(&facts, 100);
+        assert!(derived.contains(&fact(&[(true, 0), (false, 2)])));
+        //already a fact
+        assert!(!derived.contains(&fact(&[(true, 0), (false, 1)])));
+    }
+
+    /// Every literal probed costs 1, so a budget of 1 stops after the first one
+    #[test]
+    fn budget_is_per_probe(){
+        let facts = [fact(&[(false, 0), (false, 2)]), fact(&[(true, 0), (false, 1)]), fact(&[(true, 2), (false, 1)])];
+        let mut prober = Prober::new(&facts, &ProbeBudget{probes: 1});
+        assert!(prober.derive().is_empty());
+        assert_eq!(prober.remaining(), 0);
+        let mut prober = Prober::new(&facts, &ProbeBudget{probes: 6});
+        prober.derive();
+        assert_eq!(prober.remaining(), 0);
+        assert!(derive(&facts, 0).is_empty());
+    }
+
+    /// Whatever is derived holds in every model
+    #[test]
+    fn derived_facts_are_implied(){
+        let mut random = Random(0x9FB21C651E98DF25);
+        let variables = 6;
+        for _ in 0..300 {
+            let count = random.below(14);
+            let facts = random_facts(&mut random, count, 0..variables);
+            let models = models(&facts, variables);
+            for derived in derive(&facts, 100) {
+                assert!(models.iter().all(|model|holds(std::slice::from_ref(&derived), *model)), "{:?} from {:?}", derived, facts);
+            }
+        }
+    }
+}
@@ -0,0 +1,227 @@
+use std::fmt;
+
+use super::{backbone::Backbone, cardinality::{at_least_facts, at_most_facts}, expression::Expression, expression_set::Content, knowledge_base::{KnowledgeBaseFact, KnowledgeBaseLiteral}, parser::ExpressionParseError, variable::Variable, Workspace};
+
+/// Which way a count goes
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Bound{
+    Exactly,
+    AtLeast,
+    AtMost
+}
+
+/**
+    Knights and knaves puzzles, where everyone is good or evil
+    Good people only say true things and evil people only say false things
+
+    A person's variable is true when they are good,
+    so "anna says josh ^ matthew" becomes anna <> josh ^ matthew.
+    Anyone who says something is a person, :people declares the ones who don't.
+
+    anna says josh ^ matthew
+    exactly 2 evil
+    at least 1 good
+    at most 3 evil
+
+    Counts are about everyone in the puzzle, so people have to be declared before the first count.
+    They go straight to facts with totalizers instead of through an expression.
+*/
+#[derive(Debug, PartialEq)]
+enum Statement<'a>{
+    Says(&'a str, &'a str),
+    Count{bound: Bound, count: usize, good: bool}
+}
+impl<'a> Statement<'a>{
+    /// None if the input isn't a puzzle statement, then it's an expression
+    fn parse(input: &'a str)->Option<Self>{
+        let input = input.trim();
+        if let Some((speaker, statement)) = input.split_once(" says ") {
+            return Some(Self::Says(speaker.trim(), statement));
+        }
+        let (bound, rest) = if let Some(rest) = input.strip_prefix("exactly ") {
+            (Bound::Exactly, rest)
+        }else if let Some(rest) = input.strip_prefix("at least ") {
+            (Bound::AtLeast, rest)
+        }else if let Some(rest) = input.strip_prefix("at most ") {
+            (Bound::AtMost, rest)
+        }else{
+            return None;
+        };
+        let mut words = rest.split_whitespace();
+        let count = words.next()?.parse().ok()?;
+        let good = match words.next()? {
+            "good" => true,
+            "evil" => false,
+            _ => return None,
+        };
+        if words.next().is_some() {
+            return None;
+        }
+        Some(Self::Count{bound, count, good})
+    }
+}
+
+/// A count statement over the people in the puzzle when it was made
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Count{
+    bound: Bound,
+    count: usize,
+    good: bool,
+    people: Vec<Variable>
+}
+impl Count{
+    /// None if the input isn't a count
+    pub(super) fn parse(input: &str, people: &[Variable])->Option<Self>{
+        match Statement::parse(input)? {
+            Statement::Count{bound, count, good} => Some(Self{bound, count, good, people: people.to_vec()}),
+            Statement::Says(..) => None,
+        }
+    }
+    /// New variables for the totalizers come from fresh
+    pub(super) fn facts(&self, fresh: &mut impl FnMut()->Variable)->Vec<KnowledgeBaseFact>{
+        let literals: Vec<KnowledgeBaseLiteral> = self.people.iter().map(|person|KnowledgeBaseLiteral::new(!self.good, *person)).collect();
+        let mut facts = Vec::new();
+        if self.bound != Bound::AtMost {
+            at_least_facts(&literals, self.count, fresh, &mut facts);
+        }
+        if self.bound != Bound::AtLeast {
+            at_most_facts(&literals, self.count, fresh, &mut facts);
+        }
+        facts
+    }
+}
+/// The statement as it was typed, so it reads back the same
+impl fmt::Display for Count{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>)->fmt::Result{
+        let bound = match self.bound {
+            Bound::Exactly => "exactly",
+            Bound::AtLeast => "at least",
+            Bound::AtMost => "at most",
+        };
+        write!(f, "{} {} {}", bound, self.count, if self.good {"good"} else {"evil"})
+    }
+}
+
+impl Workspace{
+    /// Adds the person if they aren't one yet, new people can't come after a count
+    pub(super) fn declare_person(&mut self, name: &str)->Result<Variable, ExpressionParseError>{
+        let person = self.variable_names.variable(name)?;
+        if !self.people.contains(&person) {
+            if self.counted {
+                return Err(ExpressionParseError::PersonAfterCount(name.to_string()));
+            }
+            self.people.push(person);
+        }
+        Ok(person)
+    }
+    /// What a puzzle statement says, None if it isn't one
+    pub(super) fn parse_statement(&mut self, input: &str)->Option<Result<Content, ExpressionParseError>>{
+        Some(match Statement::parse(input)? {
+            Statement::Says(speaker, statement) => self.declare_person(speaker).and_then(|speaker|{
+                let statement = Expression::parse(statement, &mut self.variable_names)?;
+                Ok(Content::Expression(Expression::variable(speaker).iff(statement)))
+            }),
+            Statement::Count{bound, count, good} => Ok(Content::Count(Count{bound, count, good, people: self.people.clone()})),
+        })
+    }
+    /// One line for every person saying if they are good, evil or could be either
+    pub fn display_answer(&self)->String{
+        if self.people.is_empty() {
+            return "Nobody is in the puzzle, use \"anna says josh\" or :people anna, josh\n".to_string();
+        }
+        let kb = self.knowledge_base_from_all_expressions();
+        let Backbone::Literals{literals, ..} = Backbone::from_knowledge_base(&kb, &self.people) else {
+            return "Nobody can be good or evil in a way that fits every statement\n".to_string();
+        };
+
+        let mut out = String::new();
+        let mut solved = true;
+        for person in self.people.iter() {
+            let what = match literals.iter().find(|literal|literal.var() == person) {
+                Some(literal) if literal.not() => "evil",
+                Some(_) => "good",
+                None => {solved = false; "could be good or evil"},
+            };
+            out.push_str(&format!("{} is {}\n", self.variable_names.display_variable(person), what));
+        }
+        if !solved {
+            out.push_str("There is more than one answer\n");
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::{knowledge_base::KnowledgeBaseLiteral, solver::Solver};
+
+    #[test]
+    fn statements_parse(){
+        assert_eq!(Statement::parse("anna says josh ^ matthew"), Some(Statement::Says("anna", "josh ^ matthew")));
+        assert_eq!(Statement::parse("exactly 2 evil"), Some(Statement::Count{bound: Bound::Exactly, count: 2, good: false}));
+        assert_eq!(Statement::parse("at least 1 good"), Some(Statement::Count{bound: Bound::AtLeast, count: 1, good: true}));
+        assert_eq!(Statement::parse("at most two evil"), None);
+        assert_eq!(Statement::parse("anna > josh"), None);
+    }
+
+    #[test]
+    fn knights_and_knaves(){
+        //anna says they are both evil, so anna is evil and josh is good
+        let mut workspace = Workspace::new();
+        workspace.parse_line("anna says !anna & !josh").unwrap();
+        workspace.parse_line(":people josh").unwrap();
+        assert_eq!(workspace.display_answer(), "anna is evil\njosh is good\n");
+
+        workspace.parse_line("exactly 2 evil").unwrap();
+        assert_eq!(workspace.display_answer(), "Nobody can be good or evil in a way that fits every statement\n");
+    }
+
+    #[test]
+    fn counts(){
+        let mut workspace = Workspace::new();
+        workspace.parse_line(":people a, b, c, d").unwrap();
+        workspace.parse_line("exactly 2 good").unwrap();
+        //the count can have fresh variables, so each assignment of the people only has to extend to a model
+        let mut solver = Solver::new(&workspace.knowledge_base_from_all_expressions());
+        let mut count = 0;
+        for assignment in 0..16u32 {
+            let assumptions: Vec<KnowledgeBaseLiteral> = (0..4).map(|var|KnowledgeBaseLiteral::new(assignment >> var & 1 == 0, Variable::new(var))).collect();
+            if solver.solve_with_assumptions(&assumptions).is_some() {
+                assert_eq!(assignment.count_ones(), 2);
+                count += 1;
+            }
+        }
+        assert_eq!(count, 6);
+    }
+
+    /// Picking every 13 of 31 would be hundreds of millions of parts
+    #[test]
+    fn big_counts(){
+        let mut workspace = Workspace::new();
+        let people: Vec<String> = (0..31).map(|i|format!("p{}", i)).collect();
+        workspace.parse_line(&format!(":people {}", people.join(", "))).unwrap();
+        workspace.parse_line("exactly 12 evil").unwrap();
+        workspace.parse_line("p0 says p1 & !p2").unwrap();
+        assert!(workspace.expression_set.knowledge_base().facts().len() < 5000);
+
+        let model = workspace.solve().expect("12 can be evil");
+        let evil = workspace.people.iter().filter(|person|model.value(person) == Some(false)).count();
+        assert_eq!(evil, 12);
+        assert_eq!(model.value(&workspace.people[1]), model.value(&workspace.people[0]));
+    }
+
+    #[test]
+    fn people_come_before_counts(){
+        let mut workspace = Workspace::new();
+        workspace.parse_line("anna says josh").unwrap();
+        workspace.parse_line(":people josh").unwrap();
+        workspace.parse_line("at least 1 evil").unwrap();
+        assert!(matches!(workspace.parse_line(":people matthew"), Err(ExpressionParseError::PersonAfterCount(name)) if name == "matthew"));
+        assert!(matches!(workspace.parse_line("matthew says anna"), Err(ExpressionParseError::PersonAfterCount(_))));
+        //people already in the puzzle can still talk
+        workspace.parse_line("josh says anna").unwrap();
+        assert_eq!(workspace.display_answer(), "anna is evil\njosh is evil\n");
+        assert_eq!(workspace.display_expressions(), "1: anna <> josh\n2: at least 1 evil\n3: josh <> anna\n");
+    }
+}
@@ -0,0 +1,10 @@
+/// Small xorshift so generated inputs are the same every run
+pub(super) struct Random(pub(super) u64);
+impl Random{
+    pub(super) fn below(&mut self, n: u64)->u64{
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0 % n
+    }
+}
@@ -0,0 +1,190 @@
+use super::expression::{Expression, ExpressionNode};
+
+/// Rewrites a single node, anything can implement this to add its own rules
+pub trait RewriteRule{
+    fn name(&self)->&str;
+    /// None if the rule does not apply to this node
+    fn apply(&self, node: &ExpressionNode)->Option<Box<ExpressionNode>>;
+}
+
+/// A rule made from a name and a function
+pub struct NamedRule<F: Fn(&ExpressionNode)->Option<Box<ExpressionNode>>>{
+    name: String,
+    apply: F
+}
+impl<F: Fn(&ExpressionNode)->Option<Box<ExpressionNode>>> NamedRule<F>{
+    pub fn new(name: &str, apply: F)->Self{
+        Self{name: name.to_string(), apply}
+    }
+}
+impl<F: Fn(&ExpressionNode)->Option<Box<ExpressionNode>>> RewriteRule for NamedRule<F>{
+    fn name(&self)->&str{
+        &self.name
+    }
+    fn apply(&self, node: &ExpressionNode)->Option<Box<ExpressionNode>>{
+        (self.apply)(node)
+    }
+}
+
+/// One rule firing, for the trace
+#[derive(Debug, Clone)]
+pub struct RewriteStep{
+    pub rule: String,
+    pub before: Box<ExpressionNode>,
+    pub after: Box<ExpressionNode>
+}
+
+/// Rules that always match again would rewrite forever, so rewriting stops after this many
+const DEFAULT_MAX_STEPS: usize = 100_000;
+
+/**
+    Applies rules everywhere in an expression until none of them apply
+
+    Each node is rewritten until no rule matches it, then its children are visited.
+    Rewriting a child can make a rule match its parent again so whole passes repeat until nothing changes.
+    The rules are tried in order and the first that matches wins.
+    After max_steps rules have fired it stops and gives back the expression as it is.
+*/
+pub struct Rewriter{
+    rules: Vec<Box<dyn RewriteRule>>,
+    max_steps: usize
+}
+impl Default for Rewriter{
+    fn default()->Self{
+        Self::new()
+    }
+}
+impl Rewriter{
+    pub fn new()->Self{
+        Self{rules: Vec::new(), max_steps: DEFAULT_MAX_STEPS}
+    }
+    pub fn with_rule(mut self, rule: impl RewriteRule + 'static)->Self{
+        self.rules.push(Box::new(rule));
+        self
+    }
+    pub fn with_max_steps(mut self, max_steps: usize)->Self{
+        self.max_steps = max_steps;
+        self
+    }
+    /**
+        Identity        a & 1 becomes a, a | 0 becomes a
+        Annihilation    a & 0 becomes 0, a | 1 becomes 1
+        Idempotence     a & a becomes a, a | a becomes a
+        Absorption      a & (a | b) becomes a, a | (a & b) becomes a
+        Complement      a & !a becomes 0, a | !a becomes 1, !1 becomes 0
+        Double negation !!a becomes a
+        De Morgan       !(a & b) becomes !a | !b, !(a | b) becomes !a & !b
+    */
+    pub fn algebraic()->Self{
+        Self::new()
+            .with_rule(NamedRule::new("identity", identity))
+            .with_rule(NamedRule::new("annihilation", annihilation))
+            .with_rule(NamedRule::new("idempotence", idempotence))
+            .with_rule(NamedRule::new("absorption", absorption))
+            .with_rule(NamedRule::new("complement", complement))
+            .with_rule(NamedRule::new("double negation", double_negation))
+            .with_rule(NamedRule::new("de morgan", de_morgan))
+    }
+
+    /// Every rule firing is pushed onto trace if there is one
+    pub fn rewrite(&self, mut node: Box<ExpressionNode>, mut trace: Option<&mut Vec<RewriteStep>>)->Box<ExpressionNode>{
+        let mut steps = 0;
+        let mut changed = true;
+        while changed && steps < self.max_steps {
+            changed = false;
+            node = node.traverse_owned(&mut |mut node|{
+                while steps < self.max_steps {
+                    let Some((rule, after)) = self.rules.iter().find_map(|rule|rule.apply(&node).map(|after|(rule, after))) else {break};
+                    if let Some(trace) = trace.as_deref_mut() {
+                        trace.push(RewriteStep{rule: rule.name().to_string(), before: node, after: after.clone()});
+                    }
+                    node = after;
+                    steps += 1;
+                    changed = true;
+                }
+                node
+            });
+        }
+        node
+    }
+    pub fn rewrite_expression(&self, expression: Expression, trace: Option<&mut Vec<RewriteStep>>)->Expression{
+        Expression::from_node(self.rewrite(expression.node_owned(), trace))
+    }
+}
+
+fn identity(node: &ExpressionNode)->Option<Box<ExpressionNode>>{
+    match node {
+        ExpressionNode::And(a, b) if **b == ExpressionNode::Constant(true) => Some(a.clone()),
+        ExpressionNode::And(a, b) if **a == ExpressionNode::Constant(true) => Some(b.clone()),
+        ExpressionNode::Or(a, b) if **b == ExpressionNode::Constant(false) => Some(a.clone()),
+        ExpressionNode::Or(a, b) if **a == ExpressionNode::Constant(false) => Some(b.clone()),
+        _ => None,
+    }
+}
+
+fn annihilation(node: &ExpressionNode)->Option<Box<ExpressionNode>>{
+    match node {
+        ExpressionNode::And(a, b) if **a == ExpressionNode::Constant(false) || **b == ExpressionNode::Constant(false) => Some(ExpressionNode::new_constant(false)),
+        ExpressionNode::Or(a, b) if **a == ExpressionNode::Constant(true) || **b == ExpressionNode::Constant(true) => Some(ExpressionNode::new_constant(true)),
+        _ => None,
+    }
+}
+
+fn idempotence(node: &ExpressionNode)->Option<Box<ExpressionNode>>{
+    match node {
+        ExpressionNode::And(a, b) | ExpressionNode::Or(a, b) if a == b => Some(a.clone()),
+        _ => None,
+    }
+}
+
+fn absorption(node: &ExpressionNode)->Option<Box<ExpressionNode>>{
+    match node {
+        ExpressionNode::And(a, b) => {
+            let absorbs = |a: &ExpressionNode, b: &ExpressionNode|matches!(b, ExpressionNode::Or(x, y) if **x == *a || **y == *a);
+            if absorbs(a, b) {return Some(a.clone())}
+            if absorbs(b, a) {return Some(b.clone())}
+            None
+        },
+        ExpressionNode::Or(a, b) => {
+            let absorbs = |a: &ExpressionNode, b: &ExpressionNode|matches!(b, ExpressionNode::And(x, y) if **x == *a || **y == *a);
+            if absorbs(a, b) {return Some(a.clone())}
+            if absorbs(b, a) {return Some(b.clone())}
+            None
+        },
+        _ => None,
+    }
+}
+
+fn complement(node: &ExpressionNode)->Option<Box<ExpressionNode>>{
+    let complements = |a: &ExpressionNode, b: &ExpressionNode|{
+        matches!(a, ExpressionNode::Not(x) if **x == *b) || matches!(b, ExpressionNode::Not(x) if **x == *a)
+    };
+    match node {
+        ExpressionNode::And(a, b) if complements(a, b) => Some(ExpressionNode::new_constant(false)),
+        ExpressionNode::Or(a, b) if complements(a, b) => Some(ExpressionNode::new_constant(true)),
+        ExpressionNode::Not(a) => match **a {
+            ExpressionNode::Constant(value) => Some(ExpressionNode::new_constant(!value)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn double_negation(node: &ExpressionNode)->Option<Box<ExpressionNode>>{
+    match node {
+        ExpressionNode::Not(a) => match &**a {
+            ExpressionNode::Not(b) => Some(b.clone()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn de_morgan(node: &ExpressionNode)->Option<Box<ExpressionNode>>{
+    match node {
+        ExpressionNode::Not(a) if matches!(**a, ExpressionNode::And(..) | ExpressionNode::Or(..)) => {
+            Some(Box::new(node.clone()).pushdown_not())
+        },
+        _ => None,
+    }
+}
@@ -0,0 +1,340 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+
+use super::{expression::Expression, expression_set::{Content, ExpressionSet}, model_counter::VariableWeights, parser::ExpressionParser, printer::{ExpressionPrinter, OperatorStyle}, probing::ProbeBudget, puzzle::Count, solver::Solver, variable::Variable, VariableNames, Workspace};
+
+const HEADER: &str = "propositional-logic-calculator workspace";
+const VERSION: u32 = 3;
+
+/**
+    Version 3 is a header line then one line for each thing in the workspace
+
+    propositional-logic-calculator workspace 3
+    variable 0 anna
+    probability 0 0.3
+    person 0
+    style unicode
+    probe 10000
+    next 2
+    expression 1 anna > josh
+    expression rule josh | !anna
+    soft 2 5 anna & josh
+
+    Variables keep their ids, so anything numbered by id stays the same after loading.
+    Expressions are written in ascii whatever the style is, and undo history is not saved.
+    A count like exactly 2 evil is written as it was typed, it is over every person line.
+    Soft expressions have their weight before the expression.
+    Version 1 had no person lines and version 2 had no soft lines, those files still load.
+
+    A file without the header is from before there was a format,
+    when sessions were kept as the lines typed in, like testfiles/test.txt.
+    Only the expressions and statements in those are read again,
+    a directive like :save or :dot kb file could write files so the whole file is refused.
+*/
+#[derive(Debug)]
+pub enum LoadError{
+    Io(std::io::Error),
+    UnsupportedVersion(u32),
+    Corrupted{line: usize, reason: String}
+}
+impl fmt::Display for LoadError{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>)->fmt::Result{
+        match self {
+            Self::Io(error) => write!(f, "could not read the file: {}", error),
+            Self::UnsupportedVersion(version) => write!(f, "version {} is newer than this program understands (up to {})", version, VERSION),
+            Self::Corrupted{line, reason} => write!(f, "line {}: {}", line, reason),
+        }
+    }
+}
+impl std::error::Error for LoadError{
+    fn source(&self)->Option<&(dyn std::error::Error + 'static)>{
+        match self {
+            Self::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl Workspace{
+    pub fn save(&self, path: &str)->std::io::Result<()>{
+        std::fs::write(path, self.save_to_string())
+    }
+    fn save_to_string(&self)->String{
+        let mut out = format!("{} {}\n", HEADER, VERSION);
+        for var in self.variable_names.variables() {
+            out.push_str(&format!("variable {} {}\n", var.index(), self.variable_names.display_variable(&var)));
+        }
+        for (var, probability) in self.weights.probabilities() {
+            out.push_str(&format!("probability {} {}\n", var.index(), probability));
+        }
+        for person in self.people.iter() {
+            out.push_str(&format!("person {}\n", person.index()));
+        }
+        out.push_str(&format!("style {}\n", self.style.name()));
+        out.push_str(&format!("probe {}\n", self.probe_budget.probes));
+        out.push_str(&format!("next {}\n", self.expression_set.next_number()));
+
+        let printer = ExpressionPrinter::new(&self.variable_names, OperatorStyle::Ascii);
+        for entry in self.expression_set.entries() {
+            let expression = entry.display(&printer);
+            match entry.weight() {
+                Some(weight) => out.push_str(&format!("soft {} {} {}\n", entry.label(), weight, expression)),
+                None => out.push_str(&format!("expression {} {}\n", entry.label(), expression)),
+            }
+        }
+        out
+    }
+
+    pub fn load(path: &str)->Result<Self, LoadError>{
+        let contents = std::fs::read_to_string(path).map_err(LoadError::Io)?;
+        Self::load_from_string(&contents)
+    }
+    fn load_from_string(contents: &str)->Result<Self, LoadError>{
+        let Some(version) = contents.lines().next().and_then(|line|line.strip_prefix(HEADER)) else {
+            return Self::load_lines(contents);
+        };
+        let corrupted = |line: usize, reason: &str|LoadError::Corrupted{line, reason: reason.to_string()};
+        let Ok(version) = version.trim().parse::<u32>() else {return Err(corrupted(1, "the version is not a number"))};
+        if version > VERSION {
+            return Err(LoadError::UnsupportedVersion(version));
+        }
+
+        let mut names: HashMap<String, Variable> = HashMap::new();
+        //the id in the file of every variable
+        let mut ids: BTreeMap<u32, Variable> = BTreeMap::new();
+        let mut weights = VariableWeights::new();
+        let mut people = Vec::new();
+        let mut style = OperatorStyle::default();
+        let mut probe_budget = ProbeBudget::default();
+        let mut next_number = None;
+        let mut labelled = Vec::new();
+        let mut labels = HashSet::new();
+
+        for (i, line) in contents.lines().enumerate().skip(1) {
+            let number = i + 1;
+            let corrupted = |reason: &str|corrupted(number, reason);
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (kind, rest) = line.split_once(' ').unwrap_or((line, ""));
+            match kind {
+                "variable" => {
+                    let Some((id, name)) = rest.split_once(' ') else {return Err(corrupted("expected variable <id> <name>"))};
+                    let Ok(id) = id.parse::<u32>() else {return Err(corrupted("the variable id is not a number"))};
+                    //ids are handed out in order, any skipped were fresh variables which are made again
+                    if ids.last_key_value().is_some_and(|(last, _)|*last >= id) {
+                        return Err(corrupted("the variable ids don't go up"));
+                    }
+                    if !VariableNames::is_name(name) || name.to_lowercase() != name || names.contains_key(name) {
+                        return Err(corrupted(&format!("{} is not a new variable name", name)));
+                    }
+                    let var = Variable::new(names.len() as u32);
+                    names.insert(name.to_string(), var);
+                    ids.insert(id, var);
+                },
+                "probability" => {
+                    let Some((id, probability)) = rest.split_once(' ') else {return Err(corrupted("expected probability <id> <probability>"))};
+                    let Some(var) = id.parse::<u32>().ok().and_then(|id|ids.get(&id)).copied() else {
+                        return Err(corrupted("the probability is for a variable that doesn't exist"));
+                    };
+                    let Some(probability) = probability.parse::<f64>().ok().filter(|p|(0.0..=1.0).contains(p)) else {
+                        return Err(corrupted("the probability is not a number from 0 to 1"));
+                    };
+                    weights.set_probability(var, probability);
+                },
+                "person" if version >= 2 => {
+                    let Some(person) = rest.parse::<u32>().ok().and_then(|id|ids.get(&id)).copied() else {
+                        return Err(corrupted("the person is a variable that doesn't exist"));
+                    };
+                    if people.contains(&person) {
+                        return Err(corrupted("the person is already in the puzzle"));
+                    }
+                    people.push(person);
+                },
+                "style" => {
+                    let Some(loaded) = OperatorStyle::from_name(rest) else {return Err(corrupted(&format!("unknown style {}", rest)))};
+                    style = loaded;
+                },
+                "probe" => {
+                    let Ok(probes) = rest.parse() else {return Err(corrupted("the probe budget is not a number"))};
+                    probe_budget = ProbeBudget{probes};
+                },
+                "next" => {
+                    let Ok(next) = rest.parse() else {return Err(corrupted("the next number is not a number"))};
+                    next_number = Some(next);
+                },
+                "expression" | "soft" if kind == "expression" || version >= 3 => {
+                    let Some((label, rest)) = rest.split_once(' ') else {return Err(corrupted(&format!("expected {} <label> <expression>", kind)))};
+                    let (weight, input) = if kind == "soft" {
+                        let Some((weight, input)) = rest.split_once(' ') else {return Err(corrupted("expected soft <label> <weight> <expression>"))};
+                        let Some(weight) = weight.parse::<u64>().ok().filter(|weight|*weight > 0) else {return Err(corrupted("the weight is not a whole number above 0"))};
+                        (Some(weight), input)
+                    }else{
+                        (None, rest)
+                    };
+                    if label.is_empty() || !label.chars().all(|c|c.is_alphanumeric() || c == '_') || !labels.insert(label) {
+                        return Err(corrupted(&format!("{} is not a new label", label)));
+                    }
+                    //a count is written as it was typed and is over every person above it
+                    if let Some(count) = Count::parse(input, &people) {
+                        labelled.push((label.to_string(), Content::Count(count), weight));
+                        continue;
+                    }
+                    let known = names.len();
+                    let Ok(node) = ExpressionParser::parse_string(input, &mut names) else {return Err(corrupted(&format!("could not parse {}", input)))};
+                    if names.len() != known {
+                        return Err(corrupted("the expression uses a variable that wasn't listed"));
+                    }
+                    labelled.push((label.to_string(), Content::Expression(Expression::from_node(node)), weight));
+                },
+                _ => return Err(corrupted(&format!("unknown line {}", kind))),
+            }
+        }
+
+        let next_number = next_number.unwrap_or(labelled.len() + 1);
+        let mut variable_names = VariableNames{names};
+        let Ok(expression_set) = ExpressionSet::restore(labelled, next_number, &mut variable_names) else {unreachable!("labels were checked")};
+        let mut workspace = Self::new();
+        workspace.variable_names = variable_names;
+        workspace.weights = weights;
+        workspace.people = people;
+        workspace.counted = expression_set.entries().iter().any(|entry|matches!(entry.content(), Content::Count(_)));
+        workspace.style = style;
+        workspace.probe_budget = probe_budget;
+        workspace.solver = Solver::new(&expression_set.knowledge_base());
+        workspace.expression_set = expression_set;
+        Ok(workspace)
+    }
+    /// Files from before the format are the lines that were typed in
+    fn load_lines(contents: &str)->Result<Self, LoadError>{
+        let mut workspace = Self::new();
+        for (i, line) in contents.lines().enumerate() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() {
+                continue;
+            }
+            let corrupted = |reason: String|LoadError::Corrupted{line: i + 1, reason};
+            if Self::is_directive(trimmed) {
+                return Err(corrupted(format!("{} is not an expression, only expressions and statements are loaded", trimmed)));
+            }
+            let parsed = match Self::split_label(trimmed) {
+                Some((label, input)) => workspace.push_expression(Some(label), input),
+                None => workspace.parse_expression(trimmed),
+            };
+            if parsed.is_err() {
+                return Err(corrupted(format!("could not parse {}", line)));
+            }
+        }
+        Ok(workspace)
+    }
+    /// Lines parse_line does something other than add an expression with
+    fn is_directive(trimmed: &str)->bool{
+        trimmed.starts_with(':')
+            || trimmed.starts_with("p(")
+            || ["minimize", "maximize"].iter().any(|directive|trimmed.strip_prefix(directive).is_some_and(|objective|objective.trim_start().starts_with("count(")))
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn labels(workspace: &Workspace)->Vec<&str>{
+        workspace.expression_set.entries().iter().map(|entry|entry.label()).collect()
+    }
+    fn corrupted_at(contents: &str)->usize{
+        match Workspace::load_from_string(contents) {
+            Err(LoadError::Corrupted{line, ..}) => line,
+            other => panic!("expected a corrupted file, got {:?}", other.map(|_|())),
+        }
+    }
+
+    #[test]
+    fn round_trip(){
+        let mut workspace = Workspace::new();
+        for line in ["anna says !josh", "count: exactly 1 evil", "rule: josh | zed", "[4] zed & anna", "p(zed) = 0.5", ":style words", ":probe 7"] {
+            workspace.parse_line(line).unwrap();
+        }
+        let saved = workspace.save_to_string();
+        assert!(saved.contains("expression count exactly 1 evil\n"));
+        let loaded = Workspace::load_from_string(&saved).unwrap();
+        assert_eq!(loaded.save_to_string(), saved);
+        assert_eq!(labels(&loaded), ["1", "count", "rule", "2"]);
+        assert_eq!(loaded.people, workspace.people);
+        assert_eq!(loaded.expression_set.entry("count").unwrap().content(), workspace.expression_set.entry("count").unwrap().content());
+        assert!(loaded.counted);
+        assert_eq!(loaded.style, OperatorStyle::Words);
+    }
+
+    /// Only named variables are saved, fresh ones in between are made again when the expressions are
+    #[test]
+    fn fresh_variables_are_left_out(){
+        let mut workspace = Workspace::new();
+        for line in ["a ^ b ^ c ^ d", "p(e) = 0.25", ":people e", "e | !a"] {
+            workspace.parse_line(line).unwrap();
+        }
+        let e = workspace.variable_names.variable("e").unwrap();
+        assert!(e.index() > 4, "the chain made fresh variables before e");
+        let saved = workspace.save_to_string();
+        assert_eq!(saved.lines().filter(|line|line.starts_with("variable")).count(), 5);
+
+        let loaded = Workspace::load_from_string(&saved).unwrap();
+        let names: Vec<&str> = loaded.variable_names.variables().iter().map(|var|loaded.variable_names.name(var).unwrap()).collect();
+        assert_eq!(names, ["a", "b", "c", "d", "e"]);
+        let e = loaded.variable_names.names["e"];
+        assert_eq!(loaded.people, [e]);
+        assert_eq!(loaded.weights.probabilities(), [(e, 0.25)]);
+        assert_eq!(Workspace::load_from_string(&loaded.save_to_string()).unwrap().save_to_string(), loaded.save_to_string());
+    }
+
+    #[test]
+    fn every_version_loads(){
+        let version_1 = "propositional-logic-calculator workspace 1\nvariable 0 a\nvariable 1 b\nnext 2\nexpression 1 a > b\n";
+        let version_2 = "propositional-logic-calculator workspace 2\nvariable 0 a\nvariable 1 b\nperson 0\nexpression 1 a <> !b\n";
+        let version_3 = "propositional-logic-calculator workspace 3\nvariable 0 a\nvariable 1 b\nperson 0\nexpression 1 a <> !b\nsoft x 3 b\n";
+        for (contents, expected) in [(version_1, vec!["1"]), (version_2, vec!["1"]), (version_3, vec!["1", "x"])] {
+            let loaded = Workspace::load_from_string(contents).unwrap();
+            assert_eq!(labels(&loaded), expected);
+        }
+
+        //lines from later versions aren't in earlier ones
+        assert_eq!(corrupted_at("propositional-logic-calculator workspace 1\nvariable 0 a\nperson 0\n"), 3);
+        assert_eq!(corrupted_at("propositional-logic-calculator workspace 2\nvariable 0 a\nsoft 1 2 a\n"), 3);
+        assert!(matches!(Workspace::load_from_string("propositional-logic-calculator workspace 4\n"), Err(LoadError::UnsupportedVersion(4))));
+    }
+
+    #[test]
+    fn corrupted_files(){
+        let header = "propositional-logic-calculator workspace 3\n";
+        assert_eq!(corrupted_at("propositional-logic-calculator workspace three\n"), 1);
+        for (body, line) in [
+            ("variable 1 a\nvariable 0 b\n", 3),
+            ("variable 0 a\nvariable 1 a\n", 3),
+            ("variable 0 a\nexpression 1 a & b\n", 3),
+            ("variable 0 a\nexpression 1 a\nexpression 1 !a\n", 4),
+            ("variable 0 a\nexpression 1 a &\n", 3),
+            ("variable 0 a\nprobability 0 1.5\n", 3),
+            ("variable 0 a\nprobability 3 0.5\n", 3),
+            ("variable 0 a\nsoft 1 0 a\n", 3),
+            ("style fancy\n", 2),
+            ("probe lots\n", 2),
+            ("\nsomething else\n", 3),
+        ] {
+            assert_eq!(corrupted_at(&format!("{}{}", header, body)), line, "{}", body);
+        }
+    }
+
+    #[test]
+    fn legacy_files_only_load_expressions(){
+        let loaded = Workspace::load_from_string("a > b\n\nrule: b | c\n[2] !a\nanna says josh\n").unwrap();
+        assert_eq!(labels(&loaded), ["1", "rule", "2", "3"]);
+
+        let path = std::env::temp_dir().join(format!("plc-legacy-{}.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+        for directive in [format!(":save {}", path), format!(":dot kb {}", path), format!(":load {}", path), "p(a) = 0.5".to_string(), "minimize count(a)".to_string()] {
+            assert_eq!(corrupted_at(&format!("a > b\n{}\n", directive)), 2, "{}", directive);
+        }
+        assert!(!std::path::Path::new(path).exists());
+        assert_eq!(corrupted_at("a > b\na &\n"), 2);
+    }
+}
@@ -0,0 +1,227 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{knowledge_base::{KnowledgeBaseFact, KnowledgeBaseLiteral}, variable::Variable};
+
+/**
+    Does the work for KnowledgeBase::simplify without comparing every pair of facts
+
+    Each literal has an occurrence list of the facts it is in,
+    so a unit only has to look at the facts with its variable
+    and a fact only has to be compared with the facts containing its rarest literal.
+    Removed facts are left as None so the occurrence lists stay valid.
+*/
+pub(super) struct Simplifier{
+    facts: Vec<Option<KnowledgeBaseFact>>,
+    occurrences: HashMap<KnowledgeBaseLiteral, Vec<usize>>,
+    trace: Option<Vec<SimplifyStep>>
+}
+impl Simplifier{
+    pub(super) fn new(facts: Vec<KnowledgeBaseFact>)->Self{
+        Self::with_trace(facts, None)
+    }
+    /// Remembers every rule that fires, see run_explained
+    pub(super) fn explained(facts: Vec<KnowledgeBaseFact>)->Self{
+        Self::with_trace(facts, Some(Vec::new()))
+    }
+    fn with_trace(facts: Vec<KnowledgeBaseFact>, mut trace: Option<Vec<SimplifyStep>>)->Self{
+        //remove tautologies & duplicates
+        let mut seen = HashSet::new();
+        let facts: Vec<Option<KnowledgeBaseFact>> = facts
+            .into_iter()
+            .filter(|fact|{
+                let step = if fact.tautology() {
+                    SimplifyStep::Tautology(fact.clone())
+                }else if !seen.insert(fact.clone()) {
+                    SimplifyStep::Duplicate(fact.clone())
+                }else{
+                    return true;
+                };
+                if let Some(trace) = &mut trace {
+                    trace.push(step);
+                }
+                false
+            })
+            .map(Some)
+            .collect();
+
+        let mut occurrences: HashMap<KnowledgeBaseLiteral, Vec<usize>> = HashMap::new();
+        for (i, fact) in facts.iter().enumerate() {
+            let Some(fact) = fact else {continue};
+            for literal in fact.literals() {
+                occurrences.entry(literal.clone()).or_default().push(i);
+            }
+        }
+
+        Self{facts, occurrences, trace}
+    }
+    pub(super) fn run(self)->Vec<KnowledgeBaseFact>{
+        self.run_explained().0
+    }
+    /// The steps are empty unless made with explained
+    pub(super) fn run_explained(mut self)->(Vec<KnowledgeBaseFact>, Vec<SimplifyStep>){
+        let contradiction = self.facts.iter().flatten().any(|fact|fact.contradiction());
+        if contradiction || !self.propagate_units() {
+            Self::log(&mut self.trace, ||SimplifyStep::Contradiction);
+            return (vec![KnowledgeBaseFact::new(Vec::new())], self.trace.unwrap_or_default());
+        }
+        self.remove_subsumed();
+        (self.facts.into_iter().flatten().collect(), self.trace.unwrap_or_default())
+    }
+    /// Takes the trace alone so it can be used while the facts are borrowed
+    fn log(trace: &mut Option<Vec<SimplifyStep>>, step: impl FnOnce()->SimplifyStep){
+        if let Some(trace) = trace {
+            trace.push(step());
+        }
+    }
+    /// Only removes tautologies, duplicates and subsumed facts, units are left alone
+    pub(super) fn remove_redundant(facts: Vec<KnowledgeBaseFact>)->Vec<KnowledgeBaseFact>{
+        let mut simplifier = Self::new(facts);
+        simplifier.remove_subsumed();
+        simplifier.facts.into_iter().flatten().collect()
+    }
+
+    /// false if a contradiction was found
+    fn propagate_units(&mut self)->bool{
+        //the value of each unit and which fact it came from
+        let mut assigned: HashMap<Variable, (bool, usize)> = HashMap::new();
+        let mut queue: Vec<usize> = (0..self.facts.len()).rev().collect();
+
+        while let Some(i) = queue.pop() {
+            let Some(literal) = self.facts[i].as_ref().and_then(|fact|fact.is_unit()).cloned() else {continue};
+
+            match assigned.get(literal.var()) {
+                Some((value, _)) if *value == literal.not() => return false,
+                Some((_, j)) if *j == i => continue,
+                //another fact already is this unit
+                Some(_) => {
+                    Self::log(&mut self.trace, ||SimplifyStep::Duplicate(KnowledgeBaseFact::new(vec![literal.clone()])));
+                    self.facts[i] = None;
+                    continue;
+                },
+                None => {assigned.insert(*literal.var(), (!literal.not(), i));},
+            }
+
+            //[x], [x, y] becomes [x]
+            for j in self.occurrences.get(&literal).into_iter().flatten() {
+                if *j == i {
+                    continue;
+                }
+                if let Some(removed) = self.facts[*j].take() {
+                    Self::log(&mut self.trace, ||SimplifyStep::Satisfied{unit: literal.clone(), removed});
+                }
+            }
+
+            //[x], [!x, y] becomes [x], [y]
+            let negated = literal.negated();
+            for j in self.occurrences.get(&negated).into_iter().flatten() {
+                let Some(fact) = &mut self.facts[*j] else {continue};
+                let before = self.trace.is_some().then(||fact.clone());
+                fact.filter_literal(&negated);
+                let (contradiction, unit) = (fact.contradiction(), fact.is_unit().is_some());
+                if let Some(before) = before {
+                    let after = fact.clone();
+                    Self::log(&mut self.trace, ||SimplifyStep::Propagated{unit: literal.clone(), before, after});
+                }
+                if contradiction {
+                    return false;
+                }
+                if unit {
+                    queue.push(*j);
+                }
+            }
+        }
+        true
+    }
+
+    /// [y], [!x, y] becomes [y]
+    fn remove_subsumed(&mut self){
+        let mut order: Vec<usize> = (0..self.facts.len()).filter(|i|self.facts[*i].is_some()).collect();
+        order.sort_by_key(|i|self.facts[*i].as_ref().map(|fact|fact.len()));
+
+        for i in order {
+            let Some(fact) = self.facts[i].clone() else {continue};
+            let Some(rarest) = fact
+                .literals()
+                .iter()
+                .min_by_key(|literal|self.occurrences.get(*literal).map(|list|list.len()))
+            else {continue};
+
+            for j in self.occurrences.get(rarest).into_iter().flatten() {
+                if *j == i {
+                    continue;
+                }
+                if self.facts[*j].as_ref().is_some_and(|other|fact.is_subset(other)) {
+                    let Some(removed) = self.facts[*j].take() else {continue};
+                    Self::log(&mut self.trace, ||SimplifyStep::Subsumed{by: fact.clone(), removed});
+                }
+            }
+        }
+    }
+}
+
+/// A rule firing while simplifying, for explaining
+#[derive(Debug, Clone)]
+pub(super) enum SimplifyStep{
+    /// [x, !x] was removed
+    Tautology(KnowledgeBaseFact),
+    /// A second copy was removed
+    Duplicate(KnowledgeBaseFact),
+    /// [x], [x, y] becomes [x]
+    Satisfied{unit: KnowledgeBaseLiteral, removed: KnowledgeBaseFact},
+    /// [x], [!x, y] becomes [x], [y]
+    Propagated{unit: KnowledgeBaseLiteral, before: KnowledgeBaseFact, after: KnowledgeBaseFact},
+    /// [y], [!x, y] becomes [y]
+    Subsumed{by: KnowledgeBaseFact, removed: KnowledgeBaseFact},
+    /// An empty fact, everything is replaced with it
+    Contradiction
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::{benchmark::{generate, simplify_quadratic}, test_helpers::Random};
+
+    fn fact(literals: &[(bool, u32)])->KnowledgeBaseFact{
+        KnowledgeBaseFact::new(literals.iter().map(|(not, var)|KnowledgeBaseLiteral::new(*not, Variable::new(*var))).collect())
+    }
+    fn sorted(mut facts: Vec<KnowledgeBaseFact>)->Vec<KnowledgeBaseFact>{
+        facts.sort_by(|a, b|a.literals().cmp(b.literals()));
+        facts
+    }
+
+    #[test]
+    fn rules(){
+        //[0, !0] is a tautology and [1, 2] is a duplicate
+        let facts = vec![fact(&[(false, 0), (true, 0)]), fact(&[(false, 1), (false, 2)]), fact(&[(false, 1), (false, 2)])];
+        assert_eq!(Simplifier::new(facts).run(), vec![fact(&[(false, 1), (false, 2)])]);
+
+        //[0] satisfies [0, 1] and takes !0 out of [!0, 2], which then satisfies [2, 3]
+        let facts = vec![fact(&[(false, 0)]), fact(&[(false, 0), (false, 1)]), fact(&[(true, 0), (false, 2)]), fact(&[(false, 2), (false, 3)])];
+        assert_eq!(sorted(Simplifier::new(facts).run()), vec![fact(&[(false, 0)]), fact(&[(false, 2)])]);
+
+        //[1, 2] subsumes [!0, 1, 2]
+        let facts = vec![fact(&[(true, 0), (false, 1), (false, 2)]), fact(&[(false, 1), (false, 2)])];
+        assert_eq!(Simplifier::new(facts).run(), vec![fact(&[(false, 1), (false, 2)])]);
+
+        let facts = vec![fact(&[(false, 0)]), fact(&[(true, 0), (false, 1)]), fact(&[(true, 1)])];
+        assert_eq!(Simplifier::new(facts).run(), vec![fact(&[])]);
+    }
+
+    /// Gives the same facts as the old pairwise simplifier
+    #[test]
+    fn matches_quadratic(){
+        for size in [50, 100, 250, 500] {
+            let facts = generate(size, &mut Random(0x2545F4914F6CDD1D ^ size as u64));
+            assert_eq!(sorted(Simplifier::new(facts.clone()).run()), sorted(simplify_quadratic(facts)), "{} facts", size);
+        }
+    }
+
+    #[test]
+    fn explained_matches_run(){
+        let facts = vec![fact(&[(false, 0)]), fact(&[(false, 0), (false, 1)]), fact(&[(true, 0), (false, 2)]), fact(&[(false, 2), (false, 2)])];
+        let (explained, steps) = Simplifier::explained(facts.clone()).run_explained();
+        assert_eq!(explained, Simplifier::new(facts).run());
+        assert!(steps.iter().any(|step|matches!(step, SimplifyStep::Satisfied{..})));
+        assert!(steps.iter().any(|step|matches!(step, SimplifyStep::Propagated{..})));
+    }
+}
@@ -0,0 +1,611 @@
+use std::collections::HashMap;
+
+use super::{knowledge_base::{KnowledgeBaseFact, KnowledgeBaseLiteral}, variable::Variable, KnowledgeBase, VariableNames};
+
+/// A satisfying assignment
+/// Variables that are missing can be either true or false
+#[derive(Debug, Clone, Default)]
+pub struct Model{
+    values: HashMap<Variable, bool>
+}
+impl Model{
+    pub fn value(&self, var: &Variable)->Option<bool>{
+        self.values.get(var).copied()
+    }
+    pub(super) fn set(&mut self, var: Variable, value: bool){
+        self.values.insert(var, value);
+    }
+    pub(super) fn display(&self, vars: &VariableNames)->String{
+        let mut out = String::new();
+        for var in vars.variables() {
+            let value = match self.value(&var) {
+                Some(true) => "true",
+                Some(false) => "false",
+                None => "free",
+            };
+            out.push_str(&vars.display_variable(&var));
+            out.push_str(": ");
+            out.push_str(value);
+            out.push('\n');
+        }
+        out
+    }
+}
+
+/**
+    Conflict driven clause learning, kept alive between calls
+
+    Every fact watches two of its literals and is only looked at when one of them becomes false.
+    When a conflict is found the reason is turned into a learned fact
+    and the search jumps back to where that fact would have propagated.
+    Learned facts are kept so later calls don't have to find them again.
+
+    Assumptions are decided first, one per level.
+    If one of them ends up false the assumptions that caused it are kept in failed_assumptions.
+
+    Literals are numbered 2 * variable + not, so literal ^ 1 is its negation.
+    Between calls everything above level 0 is undone, so facts can be added any time.
+    Only variables in a fact or an assumption are decided and put in models,
+    numbers skipped in between stay out.
+*/
+pub struct Solver{
+    facts: Vec<Vec<usize>>,
+    watches: Vec<Vec<usize>>,
+    values: Vec<Option<bool>>,
+    levels: Vec<usize>,
+    reasons: Vec<Option<usize>>,
+    trail: Vec<usize>,
+    level_starts: Vec<usize>,
+    propagated: usize,
+    activity: Vec<f64>,
+    activity_increment: f64,
+    order: VariableOrder,
+    occurs: Vec<bool>,
+    phases: Vec<bool>,
+    failed: Vec<KnowledgeBaseLiteral>,
+    //false once there is a contradiction without assumptions
+    satisfiable: bool
+}
+impl Solver{
+    pub fn new(kb: &KnowledgeBase)->Self{
+        let mut solver = Self{
+            facts: Vec::new(),
+            watches: Vec::new(),
+            values: Vec::new(),
+            levels: Vec::new(),
+            reasons: Vec::new(),
+            trail: Vec::new(),
+            level_starts: Vec::new(),
+            propagated: 0,
+            activity: Vec::new(),
+            activity_increment: 1.0,
+            order: VariableOrder::default(),
+            occurs: Vec::new(),
+            phases: Vec::new(),
+            failed: Vec::new(),
+            satisfiable: true
+        };
+        for fact in kb.facts() {
+            solver.add_fact(fact);
+        }
+        solver
+    }
+    pub fn solve(&mut self)->Option<Model>{
+        self.solve_with_assumptions(&[])
+    }
+    /// The assumptions that made the last solve_with_assumptions fail
+    /// Empty if it didn't fail or the facts are a contradiction without any assumptions
+    pub fn failed_assumptions(&self)->&[KnowledgeBaseLiteral]{
+        &self.failed
+    }
+    /// Can be called between solves, learned facts are kept
+    pub fn add_fact(&mut self, fact: &KnowledgeBaseFact){
+        if !self.satisfiable || fact.tautology() {
+            return;
+        }
+        for literal in fact.literals() {
+            self.add_variable(literal.var());
+        }
+        //drop literals already false and skip the fact if one is already true
+        let mut literals = Vec::new();
+        for literal in fact.literals().iter().map(Self::code) {
+            match self.value(literal) {
+                Some(true) => return,
+                Some(false) => {},
+                None => literals.push(literal),
+            }
+        }
+
+        match literals.len() {
+            0 => self.satisfiable = false,
+            1 => {
+                self.assign(literals[0], None);
+                if self.propagate().is_some() {
+                    self.satisfiable = false;
+                }
+            },
+            _ => {self.push_fact(literals);},
+        }
+    }
+    /// Finds a model where every assumption is true
+    pub fn solve_with_assumptions(&mut self, assumptions: &[KnowledgeBaseLiteral])->Option<Model>{
+        self.failed.clear();
+        if !self.satisfiable {
+            return None;
+        }
+        for assumption in assumptions {
+            self.add_variable(assumption.var());
+        }
+        let assumptions: Vec<usize> = assumptions.iter().map(Self::code).collect();
+
+        let mut conflicts = 0;
+        let mut restart_at = 100;
+        loop {
+            if let Some(conflict) = self.propagate() {
+                if self.level() == 0 {
+                    self.satisfiable = false;
+                    return None;
+                }
+                conflicts += 1;
+                let (learned, level) = self.analyze(conflict);
+                self.backtrack(level);
+                let reason = if learned.len() > 1 {Some(self.push_fact(learned.clone()))} else {None};
+                self.assign(learned[0], reason);
+                self.activity_increment /= 0.95;
+                continue;
+            }
+
+            if conflicts >= restart_at {
+                restart_at += restart_at / 2;
+                self.backtrack(0);
+            }
+
+            //assumptions are decided first, one per level
+            let mut decision = None;
+            while self.level() < assumptions.len() {
+                let assumption = assumptions[self.level()];
+                match self.value(assumption) {
+                    Some(true) => self.level_starts.push(self.trail.len()),
+                    Some(false) => {
+                        self.failed = self.analyze_final(assumption);
+                        self.backtrack(0);
+                        return None;
+                    },
+                    None => {
+                        decision = Some(assumption);
+                        break;
+                    },
+                }
+            }
+
+            let Some(decision) = decision.or_else(||self.pick_branch()) else {
+                let mut model = Model::default();
+                for (var, value) in self.values.iter().enumerate() {
+                    if let (Some(value), true) = (value, self.occurs[var]) {
+                        model.set(Variable::new(var as u32), *value);
+                    }
+                }
+                self.backtrack(0);
+                return Some(model);
+            };
+            self.level_starts.push(self.trail.len());
+            self.assign(decision, None);
+        }
+    }
+
+    fn code(literal: &KnowledgeBaseLiteral)->usize{
+        literal.var().index() * 2 + literal.not() as usize
+    }
+    fn literal(code: usize)->KnowledgeBaseLiteral{
+        KnowledgeBaseLiteral::new(code & 1 == 1, Variable::new((code >> 1) as u32))
+    }
+    fn add_variable(&mut self, var: &Variable){
+        while self.values.len() <= var.index() {
+            self.values.push(None);
+            self.levels.push(0);
+            self.reasons.push(None);
+            self.activity.push(0.0);
+            self.occurs.push(false);
+            self.phases.push(false);
+            self.watches.push(Vec::new());
+            self.watches.push(Vec::new());
+            self.order.grow();
+        }
+        let var = var.index();
+        if !self.occurs[var] {
+            self.occurs[var] = true;
+            if self.values[var].is_none() {
+                self.order.insert(var, &self.activity);
+            }
+        }
+    }
+    /// Takes values alone so it can be used while a fact is borrowed
+    fn value_in(values: &[Option<bool>], literal: usize)->Option<bool>{
+        values[literal >> 1].map(|value|value != (literal & 1 == 1))
+    }
+    fn value(&self, literal: usize)->Option<bool>{
+        Self::value_in(&self.values, literal)
+    }
+    fn level(&self)->usize{
+        self.level_starts.len()
+    }
+    /// The first two literals are watched
+    fn push_fact(&mut self, literals: Vec<usize>)->usize{
+        let index = self.facts.len();
+        self.watches[literals[0]].push(index);
+        self.watches[literals[1]].push(index);
+        self.facts.push(literals);
+        index
+    }
+    fn assign(&mut self, literal: usize, reason: Option<usize>){
+        let var = literal >> 1;
+        self.values[var] = Some(literal & 1 == 0);
+        self.levels[var] = self.level();
+        self.reasons[var] = reason;
+        self.trail.push(literal);
+    }
+    fn backtrack(&mut self, level: usize){
+        if self.level() <= level {
+            return;
+        }
+        let start = self.level_starts[level];
+        for literal in self.trail.drain(start..) {
+            let var = literal >> 1;
+            self.phases[var] = self.values[var] == Some(true);
+            self.values[var] = None;
+            self.reasons[var] = None;
+            self.order.insert(var, &self.activity);
+        }
+        self.level_starts.truncate(level);
+        self.propagated = start;
+    }
+
+    /// The fact that became false if there is a conflict
+    /// A fact that propagates has the propagated literal first
+    fn propagate(&mut self)->Option<usize>{
+        while self.propagated < self.trail.len() {
+            let falsified = self.trail[self.propagated] ^ 1;
+            self.propagated += 1;
+
+            let mut watching = std::mem::take(&mut self.watches[falsified]);
+            let mut i = 0;
+            while i < watching.len() {
+                let index = watching[i];
+                let fact = &mut self.facts[index];
+                if fact[0] == falsified {
+                    fact.swap(0, 1);
+                }
+                if Self::value_in(&self.values, fact[0]) == Some(true) {
+                    i += 1;
+                    continue;
+                }
+
+                //watch another literal that isn't false
+                let replacement = (2..fact.len()).find(|k|Self::value_in(&self.values, fact[*k]) != Some(false));
+                if let Some(k) = replacement {
+                    fact.swap(1, k);
+                    self.watches[fact[1]].push(index);
+                    watching.swap_remove(i);
+                    continue;
+                }
+
+                let first = fact[0];
+                if Self::value_in(&self.values, first) == Some(false) {
+                    self.watches[falsified] = watching;
+                    self.propagated = self.trail.len();
+                    return Some(index);
+                }
+                self.assign(first, Some(index));
+                i += 1;
+            }
+            self.watches[falsified] = watching;
+        }
+        None
+    }
+
+    /**
+        Resolves the conflict with the reasons of the literals assigned at this level
+        until only one literal of this level is left, the first unique implication point
+        Returns the learned fact with that literal first, and the level to jump back to
+    */
+    fn analyze(&mut self, conflict: usize)->(Vec<usize>, usize){
+        let mut seen = vec![false; self.values.len()];
+        let mut learned = vec![0];
+        let mut at_this_level = 0;
+        let mut index = self.trail.len();
+        let mut fact = conflict;
+        let mut resolved: Option<usize> = None;
+
+        loop {
+            //a reason has the literal it propagated first
+            let skip = if resolved.is_some() {1} else {0};
+            for k in skip..self.facts[fact].len() {
+                let literal = self.facts[fact][k];
+                let var = literal >> 1;
+                if seen[var] || self.levels[var] == 0 {
+                    continue;
+                }
+                seen[var] = true;
+                self.bump(var);
+                if self.levels[var] == self.level() {
+                    at_this_level += 1;
+                }else{
+                    learned.push(literal);
+                }
+            }
+
+            //the latest literal on the trail that is part of the conflict
+            loop {
+                index -= 1;
+                if seen[self.trail[index] >> 1] {
+                    break;
+                }
+            }
+            let literal = self.trail[index];
+            seen[literal >> 1] = false;
+            at_this_level -= 1;
+            if at_this_level == 0 {
+                learned[0] = literal ^ 1;
+                break;
+            }
+            resolved = Some(literal);
+            let Some(reason) = self.reasons[literal >> 1] else {unreachable!("only the decision has no reason")};
+            fact = reason;
+        }
+
+        //the second literal is watched so it has to be the one that gets unassigned last
+        let mut level = 0;
+        for k in 1..learned.len() {
+            let literal_level = self.levels[learned[k] >> 1];
+            if literal_level > level {
+                level = literal_level;
+                learned.swap(1, k);
+            }
+        }
+        (learned, level)
+    }
+
+    /// Which assumptions forced assumption to be false
+    /// Everything decided is an assumption here because assumptions are decided first
+    fn analyze_final(&self, assumption: usize)->Vec<KnowledgeBaseLiteral>{
+        let mut failed = vec![Self::literal(assumption)];
+        if self.level() == 0 {
+            return failed;
+        }
+        let mut seen = vec![false; self.values.len()];
+        seen[assumption >> 1] = true;
+
+        for literal in self.trail[self.level_starts[0]..].iter().rev() {
+            let var = literal >> 1;
+            if !seen[var] {
+                continue;
+            }
+            match self.reasons[var] {
+                None => failed.push(Self::literal(*literal)),
+                Some(reason) => {
+                    for other in self.facts[reason][1..].iter() {
+                        if self.levels[other >> 1] > 0 {
+                            seen[other >> 1] = true;
+                        }
+                    }
+                },
+            }
+        }
+        failed
+    }
+
+    /// Scaling every activity by the same amount keeps the order
+    fn bump(&mut self, var: usize){
+        self.activity[var] += self.activity_increment;
+        if self.activity[var] > 1e100 {
+            for activity in self.activity.iter_mut() {
+                *activity *= 1e-100;
+            }
+            self.activity_increment *= 1e-100;
+        }
+        self.order.increased(var, &self.activity);
+    }
+    /// The unassigned variable in the most recent conflicts, with the value it last had
+    /// Assigned variables popped on the way are put back when backtracking unassigns them
+    fn pick_branch(&mut self)->Option<usize>{
+        while let Some(var) = self.order.pop(&self.activity) {
+            if self.values[var].is_none() {
+                return Some(var * 2 + !self.phases[var] as usize);
+            }
+        }
+        None
+    }
+}
+
+/// Binary max heap of variables by activity, knowing where each one is so a bump can move it up
+#[derive(Default)]
+struct VariableOrder{
+    heap: Vec<usize>,
+    positions: Vec<Option<usize>>
+}
+impl VariableOrder{
+    fn grow(&mut self){
+        self.positions.push(None);
+    }
+    fn insert(&mut self, var: usize, activity: &[f64]){
+        if self.positions[var].is_some() {
+            return;
+        }
+        self.heap.push(var);
+        self.positions[var] = Some(self.heap.len() - 1);
+        self.sift_up(self.heap.len() - 1, activity);
+    }
+    fn increased(&mut self, var: usize, activity: &[f64]){
+        if let Some(position) = self.positions[var] {
+            self.sift_up(position, activity);
+        }
+    }
+    fn pop(&mut self, activity: &[f64])->Option<usize>{
+        let top = *self.heap.first()?;
+        let Some(last) = self.heap.pop() else {unreachable!("it has a first")};
+        self.positions[top] = None;
+        if !self.heap.is_empty() {
+            self.heap[0] = last;
+            self.positions[last] = Some(0);
+            self.sift_down(0, activity);
+        }
+        Some(top)
+    }
+    fn swap(&mut self, a: usize, b: usize){
+        self.heap.swap(a, b);
+        self.positions[self.heap[a]] = Some(a);
+        self.positions[self.heap[b]] = Some(b);
+    }
+    fn sift_up(&mut self, mut position: usize, activity: &[f64]){
+        while position > 0 {
+            let parent = (position - 1) / 2;
+            if activity[self.heap[parent]] >= activity[self.heap[position]] {
+                break;
+            }
+            self.swap(parent, position);
+            position = parent;
+        }
+    }
+    fn sift_down(&mut self, mut position: usize, activity: &[f64]){
+        loop {
+            let mut largest = position;
+            for child in [2 * position + 1, 2 * position + 2] {
+                if child < self.heap.len() && activity[self.heap[child]] > activity[self.heap[largest]] {
+                    largest = child;
+                }
+            }
+            if largest == position {
+                break;
+            }
+            self.swap(largest, position);
+            position = largest;
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::test_helpers::{literal_holds, model_holds, models, random_facts, random_literal, Random};
+
+    fn literal(not: bool, var: u32)->KnowledgeBaseLiteral{
+        KnowledgeBaseLiteral::new(not, Variable::new(var))
+    }
+
+    /// Enough facts over few variables that most runs need conflicts and learning
+    #[test]
+    fn matches_brute_force(){
+        let mut random = Random(0xE7037ED1A0B428DB);
+        let variables = 8;
+        for _ in 0..500 {
+            let count = random.below(40);
+            let facts = random_facts(&mut random, count, 0..variables);
+            let model = Solver::new(&KnowledgeBase::from_facts(facts.clone())).solve();
+            assert_eq!(model.is_some(), !models(&facts, variables).is_empty());
+            if let Some(model) = model {
+                assert!(model_holds(&facts, &model));
+            }
+        }
+    }
+
+    /// Facts added between solves count, learned ones carry over
+    #[test]
+    fn incremental(){
+        let mut random = Random(0x8EBC6AF09C88C6E3);
+        let variables = 7;
+        for _ in 0..100 {
+            let mut solver = Solver::new(&KnowledgeBase::tautology());
+            let mut facts = Vec::new();
+            for fact in random_facts(&mut random, 30, 0..variables) {
+                solver.add_fact(&fact);
+                facts.push(fact);
+                let model = solver.solve();
+                assert_eq!(model.is_some(), !models(&facts, variables).is_empty());
+                if let Some(model) = model {
+                    assert!(model_holds(&facts, &model));
+                }
+            }
+        }
+    }
+
+    /// The failed assumptions are some of the assumptions and contradict the facts on their own
+    #[test]
+    fn failed_assumptions_are_a_core(){
+        let mut random = Random(0x589965CC75374CC3);
+        let variables = 7;
+        for _ in 0..500 {
+            let count = random.below(20);
+            let facts = random_facts(&mut random, count, 0..variables);
+            let mut assumptions: Vec<KnowledgeBaseLiteral> = Vec::new();
+            for _ in 0..random.below(5) {
+                let assumption = random_literal(&mut random, 0..variables);
+                if !assumptions.iter().any(|other|other.var() == assumption.var()) {
+                    assumptions.push(assumption);
+                }
+            }
+            let with = |assumed: &[KnowledgeBaseLiteral]|models(&facts, variables)
+                .into_iter()
+                .any(|assignment|assumed.iter().all(|literal|literal_holds(literal, assignment)));
+
+            let mut solver = Solver::new(&KnowledgeBase::from_facts(facts.clone()));
+            match solver.solve_with_assumptions(&assumptions) {
+                Some(model) => {
+                    assert!(model_holds(&facts, &model));
+                    assert!(assumptions.iter().all(|literal|model.value(literal.var()) == Some(!literal.not())));
+                },
+                None => {
+                    assert!(!with(&assumptions));
+                    let failed = solver.failed_assumptions().to_vec();
+                    assert!(failed.iter().all(|literal|assumptions.contains(literal)));
+                    assert!(!with(&failed));
+                    assert_eq!(failed.is_empty(), models(&facts, variables).is_empty());
+                },
+            }
+        }
+    }
+
+    /// a > b and b > !c so a and c can't both hold, d has nothing to do with it
+    #[test]
+    fn analyze_final_leaves_out_unneeded_assumptions(){
+        let facts = vec![
+            KnowledgeBaseFact::new(vec![literal(true, 0), literal(false, 1)]),
+            KnowledgeBaseFact::new(vec![literal(true, 1), literal(true, 2)]),
+            KnowledgeBaseFact::new(vec![literal(false, 3), literal(false, 4)]),
+        ];
+        let mut solver = Solver::new(&KnowledgeBase::from_facts(facts));
+        assert!(solver.solve_with_assumptions(&[literal(false, 3), literal(false, 0), literal(false, 2)]).is_none());
+        let mut failed = solver.failed_assumptions().to_vec();
+        failed.sort();
+        assert_eq!(failed, vec![literal(false, 0), literal(false, 2)]);
+
+        //the same solver still works without them
+        assert!(solver.solve_with_assumptions(&[literal(false, 0)]).is_some());
+        assert!(solver.failed_assumptions().is_empty());
+    }
+
+    /// Variables numbered between the ones used aren't in the model
+    #[test]
+    fn only_used_variables_are_in_models(){
+        let facts = vec![KnowledgeBaseFact::new(vec![literal(false, 0), literal(true, 5)])];
+        let mut solver = Solver::new(&KnowledgeBase::from_facts(facts.clone()));
+        let Some(model) = solver.solve_with_assumptions(&[literal(true, 3)]) else {panic!("it has models")};
+        assert!(model_holds(&facts, &model));
+        assert_eq!(model.value(&Variable::new(3)), Some(false));
+        for var in [1, 2, 4, 6] {
+            assert_eq!(model.value(&Variable::new(var)), None);
+        }
+    }
+
+    #[test]
+    fn order_pops_the_most_active(){
+        let activity = [3.0, 1.0, 4.0, 1.5, 9.0, 2.6];
+        let mut order = VariableOrder::default();
+        for var in 0..activity.len() {
+            order.grow();
+            order.insert(var, &activity);
+        }
+        let popped: Vec<usize> = std::iter::from_fn(||order.pop(&activity)).collect();
+        assert_eq!(popped, [4, 2, 0, 5, 3, 1]);
+    }
+}
@@ -0,0 +1,78 @@
+use std::ops::Range;
+
+use super::{expression::ExpressionNode, knowledge_base::{KnowledgeBaseFact, KnowledgeBaseLiteral}, solver::Model, variable::Variable};
+
+pub(super) use super::random::Random;
+
+/// A literal of one of vars, either sign
+pub(super) fn random_literal(random: &mut Random, vars: Range<u32>)->KnowledgeBaseLiteral{
+    let var = vars.start + random.below((vars.end - vars.start) as u64) as u32;
+    KnowledgeBaseLiteral::new(random.below(2) == 0, Variable::new(var))
+}
+
+/// count facts of 1 to 3 literals over vars
+pub(super) fn random_facts(random: &mut Random, count: u64, vars: Range<u32>)->Vec<KnowledgeBaseFact>{
+    (0..count).map(|_|{
+        let len = 1 + random.below(3);
+        KnowledgeBaseFact::new((0..len).map(|_|random_literal(random, vars.clone())).collect())
+    }).collect()
+}
+
+/// A tree of every operator over variables 0 to 4, constants included
+pub(super) fn random_node(random: &mut Random, depth: u8)->Box<ExpressionNode>{
+    if depth == 0 {
+        return ExpressionNode::new_variable(Variable::new(random.below(5) as u32));
+    }
+    let constructor = match random.below(10) {
+        0 => return ExpressionNode::new_variable(Variable::new(random.below(5) as u32)),
+        1 => return ExpressionNode::new_constant(random.below(2) == 0),
+        2 => return ExpressionNode::new_not(random_node(random, depth - 1)),
+        3 => ExpressionNode::new_and,
+        4 => ExpressionNode::new_or,
+        5 => ExpressionNode::new_xor,
+        6 => ExpressionNode::new_implies,
+        7 => ExpressionNode::new_biconditional,
+        8 => ExpressionNode::new_nand,
+        _ => ExpressionNode::new_nor,
+    };
+    constructor(random_node(random, depth - 1), random_node(random, depth - 1))
+}
+
+/// Bit i of assignment is the value of variable i
+pub(super) fn literal_holds(literal: &KnowledgeBaseLiteral, assignment: u32)->bool{
+    (assignment >> literal.var().index() & 1 == 1) != literal.not()
+}
+/// The value of node, bit i of assignment is the value of variable i
+pub(super) fn node_holds(node: &ExpressionNode, assignment: u32)->bool{
+    match node {
+        ExpressionNode::Variable(var) => assignment >> var.index() & 1 == 1,
+        ExpressionNode::Constant(value) => *value,
+        ExpressionNode::Not(a) => !node_holds(a, assignment),
+        ExpressionNode::And(a, b) => node_holds(a, assignment) && node_holds(b, assignment),
+        ExpressionNode::Or(a, b) => node_holds(a, assignment) || node_holds(b, assignment),
+        ExpressionNode::Xor(a, b) => node_holds(a, assignment) != node_holds(b, assignment),
+        ExpressionNode::Implies(a, b) => !node_holds(a, assignment) || node_holds(b, assignment),
+        ExpressionNode::Iff(a, b) => node_holds(a, assignment) == node_holds(b, assignment),
+        ExpressionNode::Nand(a, b) => !(node_holds(a, assignment) && node_holds(b, assignment)),
+        ExpressionNode::Nor(a, b) => !(node_holds(a, assignment) || node_holds(b, assignment)),
+    }
+}
+pub(super) fn holds(facts: &[KnowledgeBaseFact], assignment: u32)->bool{
+    facts.iter().all(|fact|fact.literals().iter().any(|literal|literal_holds(literal, assignment)))
+}
+/// Every assignment of the first variables that facts hold in
+pub(super) fn models(facts: &[KnowledgeBaseFact], variables: u32)->Vec<u32>{
+    (0..1u32 << variables).filter(|assignment|holds(facts, *assignment)).collect()
+}
+/// A variable the model leaves out counts as neither true nor false, so only a tautology holds without it
+pub(super) fn model_holds(facts: &[KnowledgeBaseFact], model: &Model)->bool{
+    facts.iter().all(|fact|fact.tautology() || fact.literals().iter().any(|literal|model.value(literal.var()) == Some(!literal.not())))
+}
+/// The model of assignment over the first variables
+pub(super) fn model_of(assignment: u32, variables: u32)->Model{
+    let mut model = Model::default();
+    for var in 0..variables {
+        model.set(Variable::new(var), assignment >> var & 1 == 1);
+    }
+    model
+}
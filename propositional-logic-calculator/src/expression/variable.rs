@@ -1,5 +1,6 @@
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub struct Variable(u8);
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Variable(u32);
 impl Variable{
-    pub fn new(name: u8)->Self{Self(name)}
+    pub fn new(name: u32)->Self{Self(name)}
+    pub fn index(&self)->usize{self.0 as usize}
 }
\ No newline at end of file
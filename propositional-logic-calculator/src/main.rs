@@ -1,7 +1,6 @@
-use expression::Workspace;
-
-mod expression;
+use propositional_logic_calculator::{benchmark_cnf, benchmark_examples, benchmark_simplify, Graph, Queens, Sudoku, Workspace};
 
+#[allow(clippy::needless_return)]
 fn get_user_input()->String{
     let mut line = String::new();
     std::io::stdin().read_line(&mut line).unwrap();
@@ -10,25 +9,84 @@ fn get_user_input()->String{
 
 
 fn main() {
+    if std::env::args().any(|arg|arg == "--bench-simplify") {
+        print!("{}", benchmark_simplify());
+        return;
+    }
+    if std::env::args().any(|arg|arg == "--bench-cnf") {
+        print!("{}", benchmark_cnf());
+        return;
+    }
+    if std::env::args().any(|arg|arg == "--bench-examples") {
+        print!("{}", benchmark_examples());
+        return;
+    }
+    let args: Vec<String> = std::env::args().collect();
+    //--sudoku grid.txt solves the grid, --queens 8 places 8 queens
+    if let Some(path) = args.iter().position(|arg|arg == "--sudoku").and_then(|i|args.get(i + 1)) {
+        match Sudoku::load(path) {
+            Ok(sudoku) => match sudoku.solve() {
+                Some(solved) => print!("{}", solved),
+                None => println!("The sudoku has no answer"),
+            },
+            Err(error) => println!("Could not load {}: {}", path, error),
+        }
+        return;
+    }
+    if let Some(size) = args.iter().position(|arg|arg == "--queens").and_then(|i|args.get(i + 1)) {
+        let Ok(size) = size.parse() else {
+            println!("{} is not a board size", size);
+            return;
+        };
+        match Queens::new(size).solve() {
+            Some(solved) => print!("{}", solved),
+            None => println!("{} queens can't be placed without one taking another", size),
+        }
+        return;
+    }
+    //--color graph.txt 3 colors the graph with 3 colors, without a count it finds the fewest
+    if let Some(i) = args.iter().position(|arg|arg == "--color") {
+        let Some(path) = args.get(i + 1) else {
+            println!("--color needs a graph file");
+            return;
+        };
+        let graph = match Graph::load(path) {
+            Ok(graph) => graph,
+            Err(error) => {
+                println!("Could not load {}: {}", path, error);
+                return;
+            },
+        };
+        let colors = args.get(i + 2).and_then(|colors|colors.parse::<usize>().ok());
+        let coloring = match colors {
+            Some(colors) => graph.color(colors),
+            None => graph.fewest_colors(),
+        };
+        match (coloring, colors) {
+            (Some(coloring), _) => print!("{}", graph.display_coloring(&coloring)),
+            (None, Some(colors)) => println!("There is no way to color the graph with {} colors", colors),
+            (None, None) => println!("A vertex is next to itself so the graph can't be colored"),
+        }
+        return;
+    }
     println!("Hello, world!");
     let mut workspace = Workspace::new();
 
-    // two_are_evil();
-
-    while let Ok(_) = workspace.parse_expression(get_user_input().as_str()) {}
+    //an empty line or the end of the input stops, a line that fails says why and carries on
+    loop {
+        let line = get_user_input();
+        if line.trim().is_empty() {
+            break;
+        }
+        match workspace.parse_line(&line) {
+            Ok(output) => print!("{}", output),
+            Err(error) => println!("{}", error),
+        }
+    }
     println!("Done parsing");
 
-    workspace.print_knowledge_base_from_all_expressions();
+    let kb = workspace.knowledge_base_from_all_expressions();
+    println!("Knowledge base simplified");
+    println!("{}", kb.with_names(workspace.variable_names()));
+    print!("{}", workspace.display_backbone());
 }
-
-fn two_are_evil(){
-    // let names = vec!["steph", "anna", "tim", "matthew", "fraser", "you", "josh"];
-    // let mut out = String::new();
-    // for i in 0..7{
-    //     for j in 0..7{
-    //         if i == 
-    //     }
-    // }
-    // println!("{}", out);
-}
-
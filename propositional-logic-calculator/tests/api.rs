@@ -0,0 +1,395 @@
+use propositional_logic_calculator::{Expression, ExpressionNode, ExpressionParseError, Graph, KnowledgeBase, KnowledgeBaseFact, KnowledgeBaseLiteral, LoadError, NamedRule, Queens, RewriteStep, Rewriter, Solver, Sudoku, Var, VariableNames, Workspace};
+
+fn parse(input: &str, names: &mut VariableNames)->Expression{
+    Expression::parse(input, names).expect("parses")
+}
+
+#[test]
+fn built_and_parsed_expressions_are_the_same(){
+    let mut names = VariableNames::new();
+    let a = Expression::variable(names.variable("a").unwrap());
+    let b = Expression::variable(names.variable("b").unwrap());
+    let c = Expression::variable(names.variable("c").unwrap());
+
+    let built = a.clone().implies(b.clone().and(!c.clone())).or(Expression::constant(false));
+    assert_eq!(built, parse("(a > b & !c) | 0", &mut names));
+    assert_eq!(built.with_names(&names).to_string(), "(a > b & !c) | 0");
+    assert_eq!(a.xor(b).iff(c).with_names(&names).to_string(), "a ^ b <> c");
+}
+
+#[test]
+fn operators_build_expressions(){
+    let mut names = VariableNames::new();
+    let [a, b, c] = ["a", "b", "c"].map(|name|names.var(name).unwrap());
+
+    assert_eq!(a & b | !c, parse("a & b | !c", &mut names));
+    assert_eq!((a ^ b).iff(c & true), parse("a ^ b <> c & 1", &mut names));
+    assert_eq!(a.implies(b | c).nand(!a), parse("(a > b | c) !& !a", &mut names));
+    assert_eq!(Expression::from(a), Expression::variable(a.variable()));
+}
+
+#[test]
+fn quantifiers_expand_over_indexed_variables(){
+    let mut names = VariableNames::new();
+    let [q11, q12, q21, q22] = ["q[1][1]", "q[1][2]", "q[2][1]", "q[2][2]"].map(|name|names.var(name).unwrap());
+
+    assert_eq!(parse("forall i in 1..2: exists j in 1 .. 2: q[i][j]", &mut names), (q11 | q12) & (q21 | q22));
+    assert_eq!(parse("forall i in 1..2: q[i][3-i]", &mut names), q12 & q21);
+    assert_eq!(parse("a | forall i in 2..1: x[i]", &mut names), names.var("a").unwrap() | true);
+    assert_eq!(names.variables().len(), 5, "nothing is made for an empty range");
+    assert!(matches!(Expression::parse("q[i]", &mut names), Err(ExpressionParseError::UnboundIndex(index)) if index == "i"));
+    assert!(matches!(Expression::parse("q[9223372036854775807+1]", &mut names), Err(ExpressionParseError::IndexOverflow(_))));
+    assert!(matches!(Expression::parse("q[99999999999999999999]", &mut names), Err(ExpressionParseError::IndexOverflow(_))));
+    assert!(matches!(Expression::parse("forall i in 1..10000000: x[i]", &mut names), Err(ExpressionParseError::RangeTooLarge)));
+    assert!(matches!(Expression::parse("forall i in -9223372036854775807..9223372036854775807: x[i]", &mut names), Err(ExpressionParseError::RangeTooLarge)));
+    //the limit is on every copy together, so nested and side by side ranges add up
+    let nested = std::time::Instant::now();
+    assert!(matches!(Expression::parse("forall i in 1..2000: forall j in 1..2000: forall k in 1..2000: q[i] | q[j] | q[k]", &mut names), Err(ExpressionParseError::RangeTooLarge)));
+    assert!(nested.elapsed() < std::time::Duration::from_secs(5));
+    assert!(matches!(Expression::parse("(forall i in 1..6000: x[i]) & forall i in 1..6000: y[i]", &mut names), Err(ExpressionParseError::RangeTooLarge)));
+    assert!(Expression::parse("forall i in 1..50: forall j in 1..50: x[i] | !x[j]", &mut names).is_ok());
+    assert_eq!(names.variables().len(), 55, "failed expressions leave no names behind");
+    assert_eq!(q12.variable(), names.variable("Q [1] [2]").unwrap());
+}
+
+#[test]
+fn built_encodings_go_straight_into_a_workspace(){
+    let mut workspace = Workspace::new();
+    let people: Vec<Var> = ["anna", "josh", "tim"].iter().map(|name|workspace.var(name).unwrap()).collect();
+
+    //at most one of them
+    for (i, x) in people.iter().enumerate() {
+        for y in &people[i + 1..] {
+            workspace.add_expression(None, x.nand(*y)).unwrap();
+        }
+    }
+    let at_least_one = people[1..].iter().fold(Expression::from(people[0]), |any, person|any | *person);
+    workspace.add_expression(Some("any"), at_least_one).unwrap();
+    workspace.add_expression(None, !people[0] & !people[2]).unwrap();
+
+    let mut solver = Solver::new(&workspace.knowledge_base_from_all_expressions());
+    let model = solver.solve().expect("josh can be the one");
+    assert_eq!(model.value(&people[1].variable()), Some(true));
+    assert!(matches!(workspace.add_expression(Some("any"), people[0].into()), Err(ExpressionParseError::DuplicateLabel)));
+}
+
+#[test]
+fn variables_keep_their_ids(){
+    let mut names = VariableNames::new();
+    let anna = names.variable("anna").unwrap();
+    let josh = names.variable("Josh").unwrap();
+    assert_eq!(anna.index(), 0);
+    assert_eq!(josh.index(), 1);
+    assert_eq!(names.variable("josh").unwrap(), josh);
+    assert_eq!(names.name(&josh), Some("josh"));
+    assert!(names.variable("a & b").is_err());
+}
+
+#[test]
+fn knowledge_base_simplifies(){
+    let mut names = VariableNames::new();
+    let mut kb = KnowledgeBase::from_expression(parse("a & (a > b) & (b | c)", &mut names));
+    kb.simplify();
+    assert_eq!(kb.with_names(&names).to_string(), "[a]\n[b]\n");
+}
+
+#[test]
+fn solver_is_incremental(){
+    let mut names = VariableNames::new();
+    let kb = KnowledgeBase::from_expression(parse("(a > b) & (b > c)", &mut names));
+    let a = names.variable("a").unwrap();
+    let c = names.variable("c").unwrap();
+    let d = names.variable("d").unwrap();
+
+    let mut solver = Solver::new(&kb);
+    let model = solver.solve_with_assumptions(&[KnowledgeBaseLiteral::new(false, a)]).expect("a can be true");
+    assert_eq!(model.value(&c), Some(true));
+
+    let assumptions = [
+        KnowledgeBaseLiteral::new(false, d),
+        KnowledgeBaseLiteral::new(false, a),
+        KnowledgeBaseLiteral::new(true, c),
+    ];
+    assert!(solver.solve_with_assumptions(&assumptions).is_none());
+    let mut failed = solver.failed_assumptions().to_vec();
+    failed.sort();
+    assert_eq!(failed, vec![assumptions[1].clone(), assumptions[2].clone()]);
+
+    solver.add_fact(&KnowledgeBaseFact::new(vec![KnowledgeBaseLiteral::new(true, c)]));
+    let model = solver.solve().expect("a can still be false");
+    assert_eq!(model.value(&a), Some(false));
+    assert_eq!(model.with_names(&names).to_string().lines().next(), Some("a: false"));
+
+    solver.add_fact(&KnowledgeBaseFact::new(vec![KnowledgeBaseLiteral::new(false, a)]));
+    assert!(solver.solve().is_none());
+}
+
+#[test]
+fn errors_describe_themselves(){
+    let mut names = VariableNames::new();
+    let error = Expression::parse("a &", &mut names).unwrap_err();
+    assert!(matches!(error, ExpressionParseError::General));
+    let error: Box<dyn std::error::Error> = Box::new(error);
+    assert_eq!(error.to_string(), "could not parse the input");
+
+    let error = Workspace::load("/this/file/does/not/exist").err().expect("missing file");
+    assert!(matches!(error, LoadError::Io(_)));
+    assert!(std::error::Error::source(&error).is_some());
+}
+
+#[test]
+fn workspace_saves_and_loads(){
+    let path = std::env::temp_dir().join(format!("plc-api-test-{}.txt", std::process::id()));
+    let path = path.to_str().unwrap();
+
+    let mut workspace = Workspace::new();
+    for line in ["zed | anna", "rule: anna > josh", "p(josh) = 0.25", ":style unicode"] {
+        workspace.parse_line(line).unwrap();
+    }
+    workspace.save(path).unwrap();
+    let mut loaded = Workspace::load(path).unwrap();
+    std::fs::remove_file(path).unwrap();
+
+    assert_eq!(loaded.variable_names().variables(), workspace.variable_names().variables());
+    assert_eq!(loaded.variable_names().name(&loaded.variable_names().variables()[0]), Some("zed"));
+    assert!(matches!(loaded.parse_line("rule: josh"), Err(ExpressionParseError::DuplicateLabel)));
+    loaded.parse_line(":retract rule").unwrap();
+    assert!(matches!(loaded.parse_line(":retract rule"), Err(ExpressionParseError::UnknownLabel)));
+}
+
+/// Lines give back what they print and failures come back as errors
+#[test]
+fn lines_return_their_output(){
+    let mut workspace = Workspace::new();
+    assert_eq!(workspace.parse_line("rule: anna & !josh").unwrap(), "");
+    assert_eq!(workspace.parse_line(":print").unwrap(), "rule: anna & !josh\n");
+    assert_eq!(workspace.parse_line(":solve").unwrap(), workspace.display_model());
+    assert!(workspace.parse_line(":backbone").unwrap().contains("anna"));
+    assert!(workspace.parse_line("p(anna)").unwrap().starts_with("P(anna) = 1"));
+
+    assert!(matches!(workspace.parse_line(":redo"), Err(ExpressionParseError::NothingToRedo)));
+    workspace.parse_line(":undo").unwrap();
+    assert!(matches!(workspace.parse_line(":undo"), Err(ExpressionParseError::NothingToUndo)));
+
+    let error = workspace.parse_line(":save /this/folder/does/not/exist.txt").expect_err("can't write there");
+    assert!(matches!(&error, ExpressionParseError::Write{path, ..} if path == "/this/folder/does/not/exist.txt"));
+    assert!(std::error::Error::source(&error).is_some());
+    assert!(matches!(workspace.parse_line(":load /this/file/does/not/exist"), Err(ExpressionParseError::Load{error: LoadError::Io(_), ..})));
+    assert!(matches!(workspace.parse_line(":dot kb /this/folder/does/not/exist.dot"), Err(ExpressionParseError::Write{..})));
+}
+
+#[test]
+fn rewriting_reaches_a_fixpoint(){
+    let mut names = VariableNames::new();
+    let mut steps: Vec<RewriteStep> = Vec::new();
+    let rewriter = Rewriter::algebraic();
+    let simplified = rewriter.rewrite_expression(parse("!!(a & (a | b)) | 0", &mut names), Some(&mut steps));
+    assert_eq!(simplified, parse("a", &mut names));
+    let rules: Vec<&str> = steps.iter().map(|step|step.rule.as_str()).collect();
+    assert_eq!(rules, ["identity", "double negation", "absorption"]);
+
+    //nothing is left to do
+    let mut again: Vec<RewriteStep> = Vec::new();
+    assert_eq!(rewriter.rewrite_expression(simplified.clone(), Some(&mut again)), simplified);
+    assert!(again.is_empty());
+}
+
+#[test]
+fn custom_rules_join_the_built_in_ones(){
+    let mut names = VariableNames::new();
+    let self_xor = NamedRule::new("self xor", |node: &ExpressionNode|match node {
+        ExpressionNode::Xor(a, b) if a == b => Some(Box::new(ExpressionNode::Constant(false))),
+        _ => None,
+    });
+    let rewriter = Rewriter::algebraic().with_rule(self_xor);
+    let mut steps: Vec<RewriteStep> = Vec::new();
+    let simplified = rewriter.rewrite_expression(parse("c | a & (b ^ b)", &mut names), Some(&mut steps));
+    assert_eq!(simplified, parse("c", &mut names));
+    assert!(steps.iter().any(|step|step.rule == "self xor"));
+}
+
+#[test]
+fn rules_that_never_finish_stop_at_the_step_limit(){
+    let mut names = VariableNames::new();
+    let swap = NamedRule::new("swap", |node: &ExpressionNode|match node {
+        ExpressionNode::And(a, b) => Some(Box::new(ExpressionNode::And(b.clone(), a.clone()))),
+        _ => None,
+    });
+    let mut steps: Vec<RewriteStep> = Vec::new();
+    let rewritten = Rewriter::new().with_rule(swap).with_max_steps(11).rewrite_expression(parse("a & b", &mut names), Some(&mut steps));
+    assert_eq!(steps.len(), 11);
+    assert_eq!(rewritten, parse("b & a", &mut names));
+}
+
+#[test]
+fn sudoku_solves_and_renders(){
+    let sudoku = Sudoku::load("testfiles/sudoku.txt").unwrap();
+    let solved = sudoku.solve().expect("the sudoku has an answer");
+    assert_eq!(solved.to_string(), "\
+5 3 4 | 6 7 8 | 9 1 2
+6 7 2 | 1 9 5 | 3 4 8
+1 9 8 | 3 4 2 | 5 6 7
+------+-------+------
+8 5 9 | 7 6 1 | 4 2 3
+4 2 6 | 8 5 3 | 7 9 1
+7 1 3 | 9 2 4 | 8 5 6
+------+-------+------
+9 6 1 | 5 3 7 | 2 8 4
+2 8 7 | 4 1 9 | 6 3 5
+3 4 5 | 2 8 6 | 1 7 9
+");
+    //a rendered board reads back the same
+    assert_eq!(Sudoku::parse(&sudoku.to_string()).unwrap(), sudoku);
+    assert!(matches!(Sudoku::parse("123"), Err(LoadError::Corrupted{line: 1, ..})));
+
+    //two 5s in the first row
+    let broken = Sudoku::parse(&std::fs::read_to_string("testfiles/sudoku.txt").unwrap().replacen("53..7", "53.57", 1)).unwrap();
+    assert_eq!(broken.solve(), None);
+}
+
+#[test]
+fn queens_never_attack(){
+    assert_eq!(Queens::new(3).solve(), None);
+    for size in [1, 4, 8, 12] {
+        let solved = Queens::new(size).solve().expect("there is an answer");
+        let columns = solved.columns();
+        assert_eq!(columns.len(), size);
+        for (r, c) in columns.iter().enumerate() {
+            for (other_r, other_c) in columns.iter().enumerate().skip(r + 1) {
+                assert!(c != other_c && other_r - r != c.abs_diff(*other_c));
+            }
+        }
+        assert_eq!(solved.to_string().matches('Q').count(), size);
+    }
+}
+
+#[test]
+fn graphs_color_or_prove_they_cant(){
+    let triangle = Graph::parse("a b\nb a # the same edge\nb c\nc a\nd").unwrap();
+    assert_eq!(triangle.vertices(), ["a", "b", "c", "d"]);
+    assert_eq!(triangle.edges().len(), 3);
+    assert_eq!(triangle.color(2), None);
+    let coloring = triangle.fewest_colors().unwrap();
+    for (u, v) in triangle.edges() {
+        assert_ne!(coloring[*u], coloring[*v]);
+    }
+    assert_eq!(coloring.iter().max(), Some(&2));
+
+    let petersen = Graph::load("testfiles/petersen.col").unwrap();
+    assert_eq!((petersen.vertices().len(), petersen.edges().len()), (10, 15));
+    assert_eq!(petersen.color(2), None);
+    assert!(petersen.color(3).is_some());
+
+    assert!(matches!(Graph::parse("p edge 2 1\ne 1 3"), Err(LoadError::Corrupted{line: 2, ..})));
+    assert_eq!(Graph::parse("a a").unwrap().fewest_colors(), None);
+}
+
+#[test]
+fn maxsat_breaks_the_least_weight(){
+    let mut workspace = Workspace::new();
+    let [anna, josh] = ["anna", "josh"].map(|name|workspace.var(name).unwrap());
+    workspace.add_expression(None, anna | josh).unwrap();
+    workspace.parse_line("cheap: [3] !josh").unwrap();
+    workspace.add_soft_expression(Some("dear"), !anna, 5).unwrap();
+    workspace.parse_line("[1] anna & josh").unwrap();
+    assert!(matches!(workspace.parse_line("[0] anna"), Err(ExpressionParseError::InvalidWeight)));
+
+    let solution = workspace.solve_maxsat().unwrap();
+    assert_eq!(solution.cost(), 4);
+    assert_eq!(solution.broken(), ["cheap", "2"]);
+    assert_eq!(solution.model().value(&josh.variable()), Some(true));
+
+    //soft expressions don't have to hold for the knowledge base
+    assert!(workspace.solve().is_some());
+    workspace.add_expression(None, !anna & !josh).unwrap();
+    assert!(workspace.solve_maxsat().is_none());
+}
+
+#[test]
+fn counts_are_minimized_and_maximized(){
+    let mut workspace = Workspace::new();
+    let vars = ["a", "b", "c", "d", "e"].map(|name|workspace.var(name).unwrap());
+    let [a, b, c, d, e] = vars;
+    workspace.add_expression(None, (a | b) & (b | c) & (c | d) & (!a | !d)).unwrap();
+    let literals = vars.map(|var|KnowledgeBaseLiteral::new(false, var.variable()));
+
+    let (model, fewest) = workspace.minimize_count(&literals).unwrap();
+    assert_eq!(fewest, 2);
+    assert_eq!(literals.iter().filter(|literal|model.value(literal.var()) == Some(true)).count(), 2);
+    assert_eq!(model.value(&e.variable()), Some(false));
+
+    //e isn't in any expression, it is still counted
+    let (model, most) = workspace.maximize_count(&literals).unwrap();
+    assert_eq!(most, 4);
+    assert_eq!(model.value(&e.variable()), Some(true));
+
+    workspace.parse_line("minimize count(a, !b)").unwrap();
+    //a single literal is in the model too, not left free
+    assert!(workspace.parse_line("maximize count(e)").unwrap().contains("e: true"));
+    assert!(workspace.parse_line("minimize count(a & b)").is_err());
+    workspace.add_expression(None, a & d).unwrap();
+    assert!(workspace.minimize_count(&literals).is_none());
+}
+
+#[test]
+fn prime_implicates_and_implicants(){
+    let mut workspace = Workspace::new();
+    let [a, b, c] = ["a", "b", "c"].map(|name|workspace.var(name).unwrap());
+    workspace.add_expression(None, a.implies(b) & b.implies(c)).unwrap();
+    let literal = |not, var: Var|KnowledgeBaseLiteral::new(not, var.variable());
+
+    let implicates = workspace.prime_implicates(100).unwrap();
+    assert_eq!(implicates.facts().len(), 3);
+    assert!(implicates.facts().contains(&KnowledgeBaseFact::new(vec![literal(true, a), literal(false, c)])));
+
+    //each implicant comes out sorted like a fact
+    let implicants = workspace.prime_implicants(100).unwrap();
+    assert_eq!(implicants.len(), 3);
+    for implicant in [[literal(false, b), literal(false, c)], [literal(true, a), literal(true, b)], [literal(true, a), literal(false, c)]] {
+        assert!(implicants.contains(&KnowledgeBaseFact::new(implicant.to_vec()).literals().clone()));
+    }
+    assert!(workspace.prime_implicates(2).is_none());
+}
+
+#[test]
+fn forgetting_keeps_what_the_rest_must_be(){
+    let mut workspace = Workspace::new();
+    let [anna, matthew, josh, sam] = ["anna", "matthew", "josh", "sam"].map(|name|workspace.var(name).unwrap());
+    let kb = KnowledgeBase::from_expression(anna.implies(matthew) & matthew.implies(josh) & (matthew | sam));
+    let literal = |not, var: Var|KnowledgeBaseLiteral::new(not, var.variable());
+
+    let forgotten = kb.forget(&[matthew.variable()]);
+    assert_eq!(forgotten.facts().len(), 2);
+    assert!(forgotten.facts().contains(&KnowledgeBaseFact::new(vec![literal(true, anna), literal(false, josh)])));
+    assert!(forgotten.facts().contains(&KnowledgeBaseFact::new(vec![literal(false, josh), literal(false, sam)])));
+
+    //ids don't change so names still print the same
+    let projected = kb.project(&[anna.variable(), josh.variable()]);
+    assert_eq!(projected.facts(), &vec![KnowledgeBaseFact::new(vec![literal(true, anna), literal(false, josh)])]);
+    assert!(kb.project(&[sam.variable()]).facts().is_empty());
+
+    //forgetting a name nothing uses is a mistake, not a new variable
+    workspace.add_expression(None, anna.implies(matthew)).unwrap();
+    assert_eq!(workspace.parse_line(":forget Matthew").unwrap(), "Nothing, every assignment of the rest is part of a model\n\n");
+    assert!(matches!(workspace.parse_line(":forget anna, nobody"), Err(ExpressionParseError::UnknownVariable(name)) if name == "nobody"));
+    assert!(matches!(workspace.parse_line(":project nobody"), Err(ExpressionParseError::UnknownVariable(_))));
+    assert!(workspace.variable_names().find("nobody").is_err());
+    assert_eq!(workspace.variable_names().find("JOSH").unwrap(), josh.variable());
+    assert_eq!(workspace.variable_names().variables().len(), 4);
+}
+
+#[test]
+fn interpolants_only_use_shared_variables(){
+    let mut workspace = Workspace::new();
+    workspace.parse_line("left: p & (p > q)").unwrap();
+    workspace.parse_line("mid: q > r").unwrap();
+    workspace.parse_line("right: !r & s").unwrap();
+
+    //left and mid say r, right says !r
+    let interpolant = workspace.interpolant(&["left", "mid"], &["right"]).unwrap().unwrap();
+    let r = workspace.var("r").unwrap().variable();
+    assert_eq!(KnowledgeBase::from_expression(interpolant).facts(), &vec![KnowledgeBaseFact::new(vec![KnowledgeBaseLiteral::new(false, r)])]);
+
+    assert!(workspace.interpolant(&["left"], &["mid"]).unwrap().is_none());
+    assert!(matches!(workspace.interpolant(&["left"], &["nowhere"]), Err(ExpressionParseError::UnknownLabel)));
+}